@@ -0,0 +1,273 @@
+//! The storage of the nodes of a tree.
+//!
+//! This is a generational arena in the manner of `generational_arena`, whose
+//! indices it hands out, so that an index stays valid only as long as the
+//! entry it was given for. Unlike `generational_arena`, it can start from the
+//! generation of another arena, so that the indices of one can never be valid
+//! in the other, and it can be mapped to an arena of another type in which
+//! every entry keeps its index.
+
+use generational_arena::Index;
+use std::{cmp, mem, ops};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Arena<T> {
+    entries: Vec<Entry<T>>,
+    generation: u64,
+    free_list_head: Option<usize>,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+enum Entry<T> {
+    Free { next_free: Option<usize> },
+    Occupied { generation: u64, value: T },
+}
+
+impl<T> Arena<T> {
+    /// An empty arena with room for `n` entries, and at least one.
+    pub(crate) fn with_capacity(n: usize) -> Arena<T> {
+        let mut arena = Arena {
+            entries: Vec::new(),
+            generation: 0,
+            free_list_head: None,
+            len: 0,
+        };
+        arena.reserve(cmp::max(n, 1));
+        arena
+    }
+
+    /// An empty arena with room for `n` entries, in which none of the indices
+    /// of this arena is valid.
+    pub(crate) fn successor<U>(&self, n: usize) -> Arena<U> {
+        let mut arena = Arena::with_capacity(n);
+        // Every entry of this arena is older than its generation.
+        arena.generation = self.generation + 1;
+        arena
+    }
+
+    pub(crate) fn reserve(&mut self, additional_capacity: usize) {
+        if additional_capacity == 0 {
+            return;
+        }
+        let start = self.entries.len();
+        let end = start + additional_capacity;
+        let old_head = self.free_list_head;
+        self.entries.reserve_exact(additional_capacity);
+        self.entries.extend((start..end).map(|slot| Entry::Free {
+            next_free: if slot == end - 1 { old_head } else { Some(slot + 1) },
+        }));
+        self.free_list_head = Some(start);
+    }
+
+    pub(crate) fn try_insert(&mut self, value: T) -> Result<Index, T> {
+        let slot = match self.free_list_head {
+            Some(slot) => slot,
+            None => return Err(value),
+        };
+        let entry = Entry::Occupied {
+            generation: self.generation,
+            value,
+        };
+        match mem::replace(&mut self.entries[slot], entry) {
+            Entry::Free { next_free } => self.free_list_head = next_free,
+            Entry::Occupied { .. } => panic!("corrupt free list"),
+        }
+        self.len += 1;
+        Ok(Index::from_raw_parts(slot, self.generation))
+    }
+
+    /// Insert `value`, doubling the capacity if there is no room left.
+    pub(crate) fn insert(&mut self, value: T) -> Index {
+        match self.try_insert(value) {
+            Ok(index) => index,
+            Err(value) => {
+                let capacity = self.capacity();
+                self.reserve(cmp::max(capacity, 1));
+                self.try_insert(value).ok().unwrap()
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, index: Index) -> Option<T> {
+        let (slot, generation) = index.into_raw_parts();
+        match self.entries.get(slot) {
+            Some(Entry::Occupied { generation: g, .. }) if *g == generation => {}
+            _ => return None,
+        }
+        let entry = Entry::Free {
+            next_free: self.free_list_head,
+        };
+        self.free_list_head = Some(slot);
+        self.generation += 1;
+        self.len -= 1;
+        match mem::replace(&mut self.entries[slot], entry) {
+            Entry::Occupied { value, .. } => Some(value),
+            Entry::Free { .. } => unreachable!(),
+        }
+    }
+
+    /// Remove every entry, but keep the allocation.
+    pub(crate) fn clear(&mut self) {
+        let capacity = self.capacity();
+        self.entries.clear();
+        self.entries.extend((0..capacity).map(|slot| Entry::Free {
+            next_free: if slot + 1 == capacity { None } else { Some(slot + 1) },
+        }));
+        if self.len > 0 {
+            self.generation += 1;
+        }
+        self.free_list_head = if capacity == 0 { None } else { Some(0) };
+        self.len = 0;
+    }
+
+    /// Release the free slots after the last entry, leaving every entry in its
+    /// slot.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        let used = match self.entries.iter().rposition(|entry| matches!(entry, Entry::Occupied { .. })) {
+            Some(last_slot) => last_slot + 1,
+            None => 0,
+        };
+        self.entries.truncate(used);
+        self.entries.shrink_to_fit();
+
+        // The slots released were the end of the free list, which is rebuilt.
+        self.free_list_head = None;
+        for slot in (0..used).rev() {
+            if let Entry::Free { next_free } = &mut self.entries[slot] {
+                *next_free = self.free_list_head;
+                self.free_list_head = Some(slot);
+            }
+        }
+    }
+
+    pub(crate) fn contains(&self, index: Index) -> bool {
+        self.get(index).is_some()
+    }
+
+    pub(crate) fn get(&self, index: Index) -> Option<&T> {
+        let (slot, generation) = index.into_raw_parts();
+        match self.entries.get(slot) {
+            Some(Entry::Occupied { generation: g, value }) if *g == generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, index: Index) -> Option<&mut T> {
+        let (slot, generation) = index.into_raw_parts();
+        self.entries.get_mut(slot)?.value_mut(generation)
+    }
+
+    /// Get exclusive references to the entries at two different indices.
+    ///
+    /// # Panics
+    ///
+    /// Panics if both indices are the same.
+    pub(crate) fn get2_mut(&mut self, index: Index, other_index: Index) -> (Option<&mut T>, Option<&mut T>) {
+        let (slot, generation) = index.into_raw_parts();
+        let (other_slot, other_generation) = other_index.into_raw_parts();
+        if slot == other_slot {
+            assert_ne!(generation, other_generation);
+            return if generation > other_generation {
+                (self.get_mut(index), None)
+            } else {
+                (None, self.get_mut(other_index))
+            };
+        }
+
+        let len = self.entries.len();
+        if slot >= len {
+            return (None, self.get_mut(other_index));
+        } else if other_slot >= len {
+            return (self.get_mut(index), None);
+        }
+
+        let (entry, other_entry) = if slot < other_slot {
+            let (before, after) = self.entries.split_at_mut(other_slot);
+            (&mut before[slot], &mut after[0])
+        } else {
+            let (before, after) = self.entries.split_at_mut(slot);
+            (&mut after[0], &mut before[other_slot])
+        };
+        (entry.value_mut(generation), other_entry.value_mut(other_generation))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// An arena of what `f` returns for every entry, each in the same slot and
+    /// with the same generation.
+    pub(crate) fn map<U, F>(&self, mut f: F) -> Arena<U>
+    where
+        F: FnMut(Index, &T) -> U,
+    {
+        let mut entries = Vec::with_capacity(self.entries.capacity());
+        entries.extend(self.entries.iter().enumerate().map(|(slot, entry)| match entry {
+            Entry::Free { next_free } => Entry::Free { next_free: *next_free },
+            Entry::Occupied { generation, value } => Entry::Occupied {
+                generation: *generation,
+                value: f(Index::from_raw_parts(slot, *generation), value),
+            },
+        }));
+        Arena {
+            entries,
+            generation: self.generation,
+            free_list_head: self.free_list_head,
+            len: self.len,
+        }
+    }
+
+    /// Turn this into an arena of what `f` returns for every entry, each in the
+    /// same slot and with the same generation.
+    pub(crate) fn into_map<U, F>(self, mut f: F) -> Arena<U>
+    where
+        F: FnMut(Index, T) -> U,
+    {
+        let mut entries = Vec::with_capacity(self.entries.capacity());
+        entries.extend(self.entries.into_iter().enumerate().map(|(slot, entry)| match entry {
+            Entry::Free { next_free } => Entry::Free { next_free },
+            Entry::Occupied { generation, value } => Entry::Occupied {
+                generation,
+                value: f(Index::from_raw_parts(slot, generation), value),
+            },
+        }));
+        Arena {
+            entries,
+            generation: self.generation,
+            free_list_head: self.free_list_head,
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Entry<T> {
+    fn value_mut(&mut self, generation: u64) -> Option<&mut T> {
+        match self {
+            Entry::Occupied { generation: g, value } if *g == generation => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl<T> ops::Index<Index> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, index: Index) -> &Self::Output {
+        self.get(index).expect("No element at index")
+    }
+}
+
+impl<T> ops::IndexMut<Index> for Arena<T> {
+    fn index_mut(&mut self, index: Index) -> &mut Self::Output {
+        self.get_mut(index).expect("No element at index")
+    }
+}
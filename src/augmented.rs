@@ -0,0 +1,284 @@
+//! A tree keeping an aggregate of every subtree up to date, such as the sum,
+//! the minimum or the maximum of some value over the nodes of the subtree.
+//!
+//! The aggregate of a node combines the measure of the node itself with the
+//! aggregates of its children, in order. Every change made through an
+//! `AugmentedVecTree` updates the aggregates of the changed nodes and their
+//! ancestors, so that reading the aggregate of a node takes constant time.
+//!
+//! Updating the aggregate of a node combines those of all its children again,
+//! so a change costs time proportional to the sum of the numbers of children
+//! of the nodes on the path from the changed node to the root node.
+
+use super::{MergeOutcome, VecTree};
+use generational_arena::Index;
+use std::collections::HashMap;
+use std::ops;
+
+/// A value with an associative way to combine two of them, and an identity
+/// element for it.
+pub trait Monoid {
+    /// The identity element: combining it with any value gives that value.
+    fn empty() -> Self;
+
+    /// Combine two values, `self` coming first.
+    fn combine(&self, other: &Self) -> Self;
+}
+
+/// Data whose contribution to the aggregates of its node and its ancestors
+/// is a value of type `A`.
+pub trait Measured<A> {
+    /// The contribution of this data alone.
+    fn measure(&self) -> A;
+}
+
+/// A `VecTree` which keeps the aggregate of every subtree up to date.
+///
+/// The tree can be read through [`tree`](#method.tree), while changes have to
+/// go through the methods of the `AugmentedVecTree`.
+#[derive(Clone, Debug)]
+pub struct AugmentedVecTree<T, A> {
+    tree: VecTree<T>,
+    aggregates: HashMap<Index, A>,
+}
+
+impl<T, A> Default for AugmentedVecTree<T, A> {
+    fn default() -> Self {
+        AugmentedVecTree {
+            tree: VecTree::default(),
+            aggregates: HashMap::new(),
+        }
+    }
+}
+
+impl<T, A> AugmentedVecTree<T, A>
+where
+    T: Measured<A>,
+    A: Monoid,
+{
+    /// Constructs a new, empty `AugmentedVecTree`.
+    pub fn new() -> AugmentedVecTree<T, A> {
+        AugmentedVecTree::default()
+    }
+
+    /// Constructs a new, empty `AugmentedVecTree` with the specified capacity.
+    pub fn with_capacity(n: usize) -> AugmentedVecTree<T, A> {
+        AugmentedVecTree {
+            tree: VecTree::with_capacity(n),
+            aggregates: HashMap::with_capacity(n),
+        }
+    }
+
+    /// The underlying tree, to read the nodes and walk through them.
+    pub fn tree(&self) -> &VecTree<T> {
+        &self.tree
+    }
+
+    /// Turn this into the underlying tree, dropping the aggregates.
+    pub fn into_tree(self) -> VecTree<T> {
+        self.tree
+    }
+
+    /// Get the aggregate of the subtree rooted at `node_id`, or `None` if the
+    /// node is not in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::augmented::{AugmentedVecTree, Measured, Monoid};
+    ///
+    /// struct Sum(u32);
+    ///
+    /// impl Monoid for Sum {
+    ///     fn empty() -> Self {
+    ///         Sum(0)
+    ///     }
+    ///
+    ///     fn combine(&self, other: &Self) -> Self {
+    ///         Sum(self.0 + other.0)
+    ///     }
+    /// }
+    ///
+    /// impl Measured<Sum> for u32 {
+    ///     fn measure(&self) -> Sum {
+    ///         Sum(*self)
+    ///     }
+    /// }
+    ///
+    /// let mut tree = AugmentedVecTree::new();
+    /// let root = tree.insert_root(1);
+    /// let child = tree.insert(2, root);
+    /// tree.insert(3, child);
+    ///
+    /// assert_eq!(tree.aggregate(root).unwrap().0, 6);
+    /// assert_eq!(tree.aggregate(child).unwrap().0, 5);
+    ///
+    /// *tree.get_mut(child).unwrap() = 10;
+    /// assert_eq!(tree.aggregate(root).unwrap().0, 14);
+    /// ```
+    pub fn aggregate(&self, node_id: Index) -> Option<&A> {
+        self.aggregates.get(&node_id)
+    }
+
+    /// Get a shared reference to the data of the node at index `node_id`, if
+    /// it is in the tree.
+    pub fn get(&self, node_id: Index) -> Option<&T> {
+        self.tree.get(node_id)
+    }
+
+    /// Get an exclusive reference to the data of the node at index `node_id`,
+    /// if it is in the tree.
+    ///
+    /// The aggregates are updated when the returned guard is dropped.
+    pub fn get_mut(&mut self, node_id: Index) -> Option<AggregateGuard<'_, T, A>> {
+        if self.tree.contains(node_id) {
+            Some(AggregateGuard { tree: self, node_id })
+        } else {
+            None
+        }
+    }
+
+    /// Insert `data` as the last child of `parent_id`, and return its index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent_id` is not in the tree.
+    pub fn insert(&mut self, data: T, parent_id: Index) -> Index {
+        let node_id = self.tree.insert(data, parent_id);
+        self.update_from(node_id);
+        node_id
+    }
+
+    /// Insert `data` as the root node, the previous root node becoming its
+    /// child, and return its index.
+    pub fn insert_root(&mut self, data: T) -> Index {
+        let node_id = self.tree.insert_root(data);
+        self.update_from(node_id);
+        node_id
+    }
+
+    /// Remove the node at index `node_id` along with its descendants, returning
+    /// its data, or `None` if it is not in the tree.
+    pub fn remove(&mut self, node_id: Index) -> Option<T> {
+        let parent_opt = self.tree.parent(node_id);
+        let aggregates = &mut self.aggregates;
+        let data = self.tree.remove_subtree_with(node_id, |descendant_id, _| {
+            aggregates.remove(&descendant_id);
+        })?;
+        self.aggregates.remove(&node_id);
+        if let Some(parent_id) = parent_opt {
+            self.update_from(parent_id);
+        }
+        Some(data)
+    }
+
+    /// Move the node at index `new_child_id` to be the last child of `node_id`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the nodes is not in the tree, or if `new_child_id` is
+    /// `node_id` or one of its ancestors.
+    pub fn append_child(&mut self, node_id: Index, new_child_id: Index) {
+        if self.tree.contains(node_id) && self.tree.ancestors(node_id).any(|id| id == new_child_id) {
+            panic!("A node can't be moved below itself");
+        }
+        let old_parent_opt = self.tree.parent(new_child_id);
+        self.tree.append_child(node_id, new_child_id);
+        if let Some(old_parent_id) = old_parent_opt {
+            self.update_from(old_parent_id);
+        }
+        self.update_from(node_id);
+    }
+
+    /// Fork the node at index `node_id` as [`VecTree::fork`](../struct.VecTree.html#method.fork)
+    /// does, returning the index of the new sibling.
+    pub fn fork(&mut self, node_id: Index, new_parent_data: T, new_sibling_data: T) -> Option<Index> {
+        let new_sibling_id = self.tree.fork(node_id, new_parent_data, new_sibling_data)?;
+        self.update_node(new_sibling_id);
+        self.update_from(self.tree.parent(new_sibling_id).unwrap());
+        Some(new_sibling_id)
+    }
+
+    /// Merge two nodes with the same parent as [`VecTree::merge`](../struct.VecTree.html#method.merge)
+    /// does.
+    pub fn merge(&mut self, node_id: Index, merge_into_node_id: Index) -> Option<MergeOutcome<T>> {
+        let parent_opt = self.tree.parent(node_id);
+        let outcome = self.tree.merge(node_id, merge_into_node_id)?;
+        self.aggregates.remove(&node_id);
+        if outcome.removed_parent.is_some() {
+            self.aggregates.remove(&parent_opt.unwrap());
+        }
+        self.update_from(outcome.surviving);
+        Some(outcome)
+    }
+
+    /// Compute the aggregate of the node at index `node_id` from its own measure
+    /// and the aggregates of its children.
+    fn update_node(&mut self, node_id: Index) {
+        let mut aggregate = A::empty().combine(&self.tree[node_id].measure());
+        for child_id in self.tree.children(node_id) {
+            aggregate = aggregate.combine(&self.aggregates[&child_id]);
+        }
+        self.aggregates.insert(node_id, aggregate);
+    }
+
+    /// Update the aggregates of the node at index `node_id` and its ancestors.
+    fn update_from(&mut self, node_id: Index) {
+        let mut node_opt = Some(node_id);
+        while let Some(current_id) = node_opt {
+            self.update_node(current_id);
+            node_opt = self.tree.parent(current_id);
+        }
+    }
+}
+
+impl<T, A> ops::Index<Index> for AugmentedVecTree<T, A> {
+    type Output = T;
+
+    fn index(&self, index: Index) -> &Self::Output {
+        &self.tree[index]
+    }
+}
+
+/// An exclusive reference to the data of a node of an `AugmentedVecTree`,
+/// updating the aggregates when dropped.
+pub struct AggregateGuard<'a, T, A>
+where
+    T: Measured<A>,
+    A: Monoid,
+{
+    tree: &'a mut AugmentedVecTree<T, A>,
+    node_id: Index,
+}
+
+impl<'a, T, A> ops::Deref for AggregateGuard<'a, T, A>
+where
+    T: Measured<A>,
+    A: Monoid,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.tree.tree[self.node_id]
+    }
+}
+
+impl<'a, T, A> ops::DerefMut for AggregateGuard<'a, T, A>
+where
+    T: Measured<A>,
+    A: Monoid,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.tree.tree[self.node_id]
+    }
+}
+
+impl<'a, T, A> Drop for AggregateGuard<'a, T, A>
+where
+    T: Measured<A>,
+    A: Monoid,
+{
+    fn drop(&mut self) {
+        self.tree.update_from(self.node_id);
+    }
+}
@@ -0,0 +1,248 @@
+//! An order-statistics tree over the children of a node.
+//!
+//! The children are kept in a treap ordered by position, each entry knowing
+//! the size of its subtree, so that finding the child at a given position,
+//! finding the position of a child, inserting a child and removing one all
+//! take logarithmic time.
+
+use generational_arena::Index;
+use std::collections::HashMap;
+
+// Stands for a missing entry.
+const NIL: usize = usize::MAX;
+
+#[derive(Clone, Debug)]
+struct Entry {
+    child_id: Index,
+    priority: u64,
+    left: usize,
+    right: usize,
+    parent: usize,
+    size: usize,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ChildOrder {
+    entries: Vec<Entry>,
+    free_entries: Vec<usize>,
+    entry_of: HashMap<Index, usize>,
+    root: usize,
+    seed: u64,
+}
+
+impl ChildOrder {
+    /// Build the order of the given children, in linear time.
+    pub(crate) fn new<I>(children: I) -> ChildOrder
+    where
+        I: IntoIterator<Item = Index>,
+    {
+        let mut order = ChildOrder {
+            entries: Vec::new(),
+            free_entries: Vec::new(),
+            entry_of: HashMap::new(),
+            root: NIL,
+            seed: 0,
+        };
+
+        // The treap is the Cartesian tree of the priorities, built by keeping its
+        // right spine on a stack. The entries taken off the spine are complete.
+        let mut right_spine: Vec<usize> = Vec::new();
+        for child_id in children {
+            let entry = order.new_entry(child_id);
+            let mut left = NIL;
+            while let Some(&last) = right_spine.last() {
+                if order.entries[last].priority > order.entries[entry].priority {
+                    break;
+                }
+                right_spine.pop();
+                order.update(last);
+                left = last;
+            }
+            order.entries[entry].left = left;
+            if let Some(&last) = right_spine.last() {
+                order.entries[last].right = entry;
+            }
+            right_spine.push(entry);
+        }
+        while let Some(last) = right_spine.pop() {
+            order.update(last);
+            order.root = last;
+        }
+        order.set_root(order.root);
+        order
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.size(self.root)
+    }
+
+    /// The child at `position`, counting from 0.
+    pub(crate) fn nth(&self, mut position: usize) -> Option<Index> {
+        let mut entry = self.root;
+        while entry != NIL {
+            let left_size = self.size(self.entries[entry].left);
+            if position < left_size {
+                entry = self.entries[entry].left;
+            } else if position == left_size {
+                return Some(self.entries[entry].child_id);
+            } else {
+                position -= left_size + 1;
+                entry = self.entries[entry].right;
+            }
+        }
+        None
+    }
+
+    /// The position of `child_id`, counting from 0.
+    pub(crate) fn position(&self, child_id: Index) -> Option<usize> {
+        let mut entry = *self.entry_of.get(&child_id)?;
+        let mut position = self.size(self.entries[entry].left);
+        while self.entries[entry].parent != NIL {
+            let parent = self.entries[entry].parent;
+            if self.entries[parent].right == entry {
+                position += self.size(self.entries[parent].left) + 1;
+            }
+            entry = parent;
+        }
+        Some(position)
+    }
+
+    /// Insert `child_id` at `position`, the children from there on moving one
+    /// position further.
+    pub(crate) fn insert(&mut self, position: usize, child_id: Index) {
+        let entry = self.new_entry(child_id);
+        let (before, after) = self.split(self.root, position);
+        let root = self.merge(before, entry);
+        let root = self.merge(root, after);
+        self.set_root(root);
+    }
+
+    /// Remove `child_id`, returning whether it was there.
+    pub(crate) fn remove(&mut self, child_id: Index) -> bool {
+        let position = match self.position(child_id) {
+            Some(position) => position,
+            None => return false,
+        };
+        let (before, rest) = self.split(self.root, position);
+        let (entry, after) = self.split(rest, 1);
+        let root = self.merge(before, after);
+        self.set_root(root);
+
+        self.entry_of.remove(&child_id);
+        self.free_entries.push(entry);
+        true
+    }
+
+    /// Exchange the positions of two children.
+    pub(crate) fn swap(&mut self, child_id: Index, other_child_id: Index) {
+        let entry = self.entry_of[&child_id];
+        let other_entry = self.entry_of[&other_child_id];
+        self.entries[entry].child_id = other_child_id;
+        self.entries[other_entry].child_id = child_id;
+        self.entry_of.insert(child_id, other_entry);
+        self.entry_of.insert(other_child_id, entry);
+    }
+
+    /// Store a new entry for `child_id`, linked to nothing yet.
+    fn new_entry(&mut self, child_id: Index) -> usize {
+        let entry = Entry {
+            child_id,
+            priority: self.next_priority(),
+            left: NIL,
+            right: NIL,
+            parent: NIL,
+            size: 1,
+        };
+        let entry = match self.free_entries.pop() {
+            Some(free_entry) => {
+                self.entries[free_entry] = entry;
+                free_entry
+            }
+            None => {
+                self.entries.push(entry);
+                self.entries.len() - 1
+            }
+        };
+        self.entry_of.insert(child_id, entry);
+        entry
+    }
+
+    fn size(&self, entry: usize) -> usize {
+        if entry == NIL {
+            0
+        } else {
+            self.entries[entry].size
+        }
+    }
+
+    fn set_root(&mut self, root: usize) {
+        self.root = root;
+        if root != NIL {
+            self.entries[root].parent = NIL;
+        }
+    }
+
+    /// Recompute the size of `entry`, and link its children back to it.
+    fn update(&mut self, entry: usize) {
+        let (left, right) = (self.entries[entry].left, self.entries[entry].right);
+        self.entries[entry].size = 1 + self.size(left) + self.size(right);
+        if left != NIL {
+            self.entries[left].parent = entry;
+        }
+        if right != NIL {
+            self.entries[right].parent = entry;
+        }
+    }
+
+    /// Split the treap rooted at `entry` into the first `position` children
+    /// and the others.
+    fn split(&mut self, entry: usize, position: usize) -> (usize, usize) {
+        if entry == NIL {
+            return (NIL, NIL);
+        }
+        let left = self.entries[entry].left;
+        let left_size = self.size(left);
+        if position <= left_size {
+            let (before, after) = self.split(left, position);
+            self.entries[entry].left = after;
+            self.update(entry);
+            (before, entry)
+        } else {
+            let right = self.entries[entry].right;
+            let (before, after) = self.split(right, position - left_size - 1);
+            self.entries[entry].right = before;
+            self.update(entry);
+            (entry, after)
+        }
+    }
+
+    /// Join two treaps, the children of `first` coming first.
+    fn merge(&mut self, first: usize, second: usize) -> usize {
+        if first == NIL {
+            return second;
+        }
+        if second == NIL {
+            return first;
+        }
+        if self.entries[first].priority > self.entries[second].priority {
+            let right = self.entries[first].right;
+            self.entries[first].right = self.merge(right, second);
+            self.update(first);
+            first
+        } else {
+            let left = self.entries[second].left;
+            self.entries[second].left = self.merge(first, left);
+            self.update(second);
+            second
+        }
+    }
+
+    /// The next pseudo-random priority, from SplitMix64.
+    fn next_priority(&mut self) -> u64 {
+        self.seed = self.seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
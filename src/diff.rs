@@ -0,0 +1,248 @@
+//! The differences between two trees, as a list of edits turning one into the other.
+//!
+//! Nodes of the two trees are matched by a key computed from their data, so
+//! that a node that moved around is moved rather than removed and inserted
+//! again.
+
+use super::{NodeEdge, VecTree};
+use generational_arena::Index;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// A node referred to by an edit: either a node already in the tree, or a
+/// node inserted by an earlier edit of the same patch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NodeRef {
+    /// A node that was in the tree before the patch.
+    Existing(Index),
+    /// The node inserted by the `n`th `Edit::Insert` of the patch, counting from zero.
+    Inserted(usize),
+}
+
+/// A single change to a tree.
+///
+/// Nodes are placed by their parent and the sibling they follow, `None` meaning
+/// they become the first child. A node placed without a parent becomes the root
+/// node, the previous root becoming its last child.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit<T> {
+    /// Insert a new node holding `data`.
+    Insert {
+        parent: Option<NodeRef>,
+        after: Option<NodeRef>,
+        data: T,
+    },
+    /// Move a node, along with its descendants.
+    Move {
+        node: Index,
+        parent: Option<NodeRef>,
+        after: Option<NodeRef>,
+    },
+    /// Replace the data of a node.
+    Update { node: Index, data: T },
+    /// Remove a node, along with its descendants.
+    Remove { node: Index },
+}
+
+impl<T> VecTree<T> {
+    /// Compute the edits turning this tree into `other`.
+    ///
+    /// Nodes are matched when `key_fn` gives the same key for their data; when
+    /// several nodes of a tree share a key, only the first one in tree order is
+    /// matched. Matched nodes are moved where needed and updated when their
+    /// data differ, the other nodes of `other` are inserted, and the other
+    /// nodes of this tree are removed.
+    ///
+    /// Applying the edits to this tree with [`apply_patch`](#method.apply_patch)
+    /// gives a tree equal to `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate vec_tree;
+    /// # fn main() {
+    /// use vec_tree::diff::{Edit, NodeRef};
+    ///
+    /// let mut tree = tree![("root", 0) => [("a", 1), ("b", 2)]];
+    /// let other = tree![("root", 0) => [("b", 2), ("a", 3)]];
+    ///
+    /// let patch = tree.diff(&other, |&(key, _)| key);
+    /// let root = tree.get_root_index().unwrap();
+    /// let a = tree.children(root).next().unwrap();
+    /// let b = tree.children(root).nth(1).unwrap();
+    /// assert_eq!(
+    ///     patch,
+    ///     [
+    ///         Edit::Move { node: b, parent: Some(NodeRef::Existing(root)), after: None },
+    ///         Edit::Update { node: a, data: ("a", 3) },
+    ///     ]
+    /// );
+    ///
+    /// tree.apply_patch(patch);
+    /// assert_eq!(tree, other);
+    /// # }
+    /// ```
+    pub fn diff<K, F>(&self, other: &VecTree<T>, key_fn: F) -> Vec<Edit<T>>
+    where
+        T: Clone + PartialEq,
+        K: Eq + Hash,
+        F: Fn(&T) -> K,
+    {
+        let mut edits = Vec::new();
+
+        // The nodes of this tree which may still be matched, by key.
+        let mut unmatched_by_key: HashMap<K, Index> = HashMap::new();
+        // The links of this tree, kept up to date with the edits so far,
+        // which tells whether a node already is in the right place.
+        let mut shadow: VecTree<NodeRef> = VecTree::with_capacity(self.nodes.len());
+        let mut shadow_ids: HashMap<NodeRef, Index> = HashMap::new();
+        if let Some(root_id) = self.root_index {
+            let mut shadow_parents = Vec::new();
+            for edge in self.traverse(root_id) {
+                match edge {
+                    NodeEdge::Start(node_id) => {
+                        unmatched_by_key.entry(key_fn(&self[node_id])).or_insert(node_id);
+                        let node_ref = NodeRef::Existing(node_id);
+                        let shadow_id = match shadow_parents.last() {
+                            Some(&shadow_parent_id) => shadow.insert(node_ref, shadow_parent_id),
+                            None => shadow.insert_root(node_ref),
+                        };
+                        shadow_ids.insert(node_ref, shadow_id);
+                        shadow_parents.push(shadow_id);
+                    }
+                    NodeEdge::End(_) => {
+                        shadow_parents.pop();
+                    }
+                }
+            }
+        }
+
+        // Going through `other` in tree order, its parents and previous siblings
+        // are always in place before their children and next siblings.
+        let mut matched = HashSet::new();
+        let mut node_refs: HashMap<Index, NodeRef> = HashMap::new();
+        let mut insert_count = 0;
+        if let Some(other_root_id) = other.root_index {
+            for other_node_id in other.descendants(other_root_id) {
+                let other_node = &other.nodes[other_node_id];
+                let (parent, after) = if other_node_id == other_root_id {
+                    (None, None)
+                } else {
+                    (
+                        other_node.parent.map(|parent_id| node_refs[&parent_id]),
+                        other_node.previous_sibling.map(|sibling_id| node_refs[&sibling_id]),
+                    )
+                };
+                let shadow_parent_id = parent.map(|parent| shadow_ids[&parent]);
+                let shadow_previous_sibling_id = after.map(|after| shadow_ids[&after]);
+
+                match unmatched_by_key.remove(&key_fn(&other_node.data)) {
+                    Some(node_id) => {
+                        matched.insert(node_id);
+                        let node_ref = NodeRef::Existing(node_id);
+                        node_refs.insert(other_node_id, node_ref);
+
+                        let shadow_id = shadow_ids[&node_ref];
+                        let shadow_node = &shadow.nodes[shadow_id];
+                        if shadow_node.parent != shadow_parent_id
+                            || shadow_node.previous_sibling != shadow_previous_sibling_id
+                        {
+                            shadow.place(shadow_id, shadow_parent_id, shadow_previous_sibling_id);
+                            edits.push(Edit::Move { node: node_id, parent, after });
+                        }
+                        if self[node_id] != other_node.data {
+                            edits.push(Edit::Update {
+                                node: node_id,
+                                data: other_node.data.clone(),
+                            });
+                        }
+                    }
+                    None => {
+                        let node_ref = NodeRef::Inserted(insert_count);
+                        insert_count += 1;
+                        node_refs.insert(other_node_id, node_ref);
+
+                        let shadow_id = shadow.create_node(node_ref);
+                        shadow.place(shadow_id, shadow_parent_id, shadow_previous_sibling_id);
+                        shadow_ids.insert(node_ref, shadow_id);
+                        edits.push(Edit::Insert {
+                            parent,
+                            after,
+                            data: other_node.data.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        // Every matched node has been moved out of the subtrees of the unmatched
+        // nodes, so only the topmost unmatched nodes need to be removed.
+        if let Some(root_id) = self.root_index {
+            for node_id in self.descendants(root_id) {
+                let is_topmost = match self.nodes[node_id].parent {
+                    Some(parent_id) => matched.contains(&parent_id),
+                    None => true,
+                };
+                if is_topmost && !matched.contains(&node_id) {
+                    edits.push(Edit::Remove { node: node_id });
+                }
+            }
+        }
+
+        edits
+    }
+
+    /// Apply the edits of a patch, as computed by [`diff`](#method.diff), in order.
+    ///
+    /// Returns the indices of the inserted nodes, in the order of their `Edit::Insert`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an edit refers to a node which is not in the tree, or to an
+    /// insertion that doesn't come before it in the patch.
+    pub fn apply_patch<I>(&mut self, patch: I) -> Vec<Index>
+    where
+        I: IntoIterator<Item = Edit<T>>,
+    {
+        let mut inserted: Vec<Index> = Vec::new();
+        let resolve = |inserted: &Vec<Index>, node_ref: NodeRef| match node_ref {
+            NodeRef::Existing(node_id) => node_id,
+            NodeRef::Inserted(n) => *inserted
+                .get(n)
+                .expect("The patch refers to a node it hasn't inserted yet"),
+        };
+
+        for edit in patch {
+            match edit {
+                Edit::Insert { parent, after, data } => {
+                    let parent_id = parent.map(|parent| resolve(&inserted, parent));
+                    let previous_sibling_id = after.map(|after| resolve(&inserted, after));
+                    let node_id = self.create_node(data);
+                    self.place(node_id, parent_id, previous_sibling_id);
+                    inserted.push(node_id);
+                }
+                Edit::Move { node, parent, after } => {
+                    if !self.contains(node) {
+                        panic!("The node you are trying to move is invalid");
+                    }
+                    let parent_id = parent.map(|parent| resolve(&inserted, parent));
+                    let previous_sibling_id = after.map(|after| resolve(&inserted, after));
+                    self.place(node, parent_id, previous_sibling_id);
+                }
+                Edit::Update { node, data } => {
+                    match self.get_mut(node) {
+                        Some(node_data) => *node_data = data,
+                        None => panic!("The node you are trying to update is invalid"),
+                    }
+                }
+                Edit::Remove { node } => {
+                    if self.remove(node).is_none() {
+                        panic!("The node you are trying to remove is invalid");
+                    }
+                }
+            }
+        }
+
+        inserted
+    }
+}
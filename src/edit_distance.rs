@@ -0,0 +1,242 @@
+//! The ordered tree edit distance between two subtrees, computed with the
+//! [Zhang–Shasha algorithm](https://doi.org/10.1137/0218082).
+//!
+//! The distance is the cheapest way to turn one subtree into the other by
+//! deleting nodes (their children taking their place), inserting nodes, and
+//! relabelling nodes, the cost of each operation being given by the caller.
+
+use super::{NodeEdge, VecTree};
+use generational_arena::Index;
+
+/// The result of [`VecTree::edit_distance`](../struct.VecTree.html#method.edit_distance).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeEditDistance {
+    /// The total cost of the cheapest edit script.
+    pub distance: f64,
+    /// The pairs of nodes kept by the cheapest edit script, the first of each
+    /// pair in the first subtree and the second in the other, in post-order.
+    ///
+    /// Every node missing from the mapping is deleted from the first subtree,
+    /// or inserted in the other.
+    pub mapping: Vec<(Index, Index)>,
+}
+
+/// A subtree flattened in post-order, with the position of the leftmost leaf
+/// below every node.
+struct PostOrder {
+    nodes: Vec<Index>,
+    leftmost_leaves: Vec<usize>,
+}
+
+impl PostOrder {
+    fn new<T>(tree: &VecTree<T>, node_id: Index) -> PostOrder {
+        let mut nodes = Vec::new();
+        let mut leftmost_leaves = Vec::new();
+        // The position of the first node below each node being walked through.
+        let mut starts = Vec::new();
+        for edge in tree.traverse(node_id) {
+            match edge {
+                NodeEdge::Start(_) => starts.push(nodes.len()),
+                NodeEdge::End(node_id) => {
+                    nodes.push(node_id);
+                    leftmost_leaves.push(starts.pop().unwrap());
+                }
+            }
+        }
+        PostOrder { nodes, leftmost_leaves }
+    }
+
+    /// The nodes which are the roots of their subtree, or whose leftmost leaf differs
+    /// from their parent's, in increasing order.
+    fn keyroots(&self) -> Vec<usize> {
+        let mut keyroots = Vec::new();
+        let mut seen_leaves = vec![false; self.nodes.len()];
+        for position in (0..self.nodes.len()).rev() {
+            let leaf = self.leftmost_leaves[position];
+            if !seen_leaves[leaf] {
+                seen_leaves[leaf] = true;
+                keyroots.push(position);
+            }
+        }
+        keyroots.reverse();
+        keyroots
+    }
+}
+
+struct Solver<'a, R> {
+    first: &'a PostOrder,
+    second: &'a PostOrder,
+    delete_costs: Vec<f64>,
+    insert_costs: Vec<f64>,
+    relabel_cost: R,
+    tree_distances: Vec<f64>,
+}
+
+impl<'a, R> Solver<'a, R>
+where
+    R: Fn(usize, usize) -> f64,
+{
+    fn tree_distance(&self, i: usize, j: usize) -> f64 {
+        self.tree_distances[i * self.second.nodes.len() + j]
+    }
+
+    /// The distances between the forests ending at `i` and `j`, both starting
+    /// at their leftmost leaf, as a table with a row per node of the first forest
+    /// and a column per node of the other, plus an empty first row and column.
+    ///
+    /// The distances between whole subtrees found along the way are recorded.
+    fn forest_distances(&mut self, i: usize, j: usize) -> Vec<Vec<f64>> {
+        let first_leaf = self.first.leftmost_leaves[i];
+        let second_leaf = self.second.leftmost_leaves[j];
+        let rows = i - first_leaf + 2;
+        let columns = j - second_leaf + 2;
+
+        let mut distances = vec![vec![0.0; columns]; rows];
+        for x in 1..rows {
+            distances[x][0] = distances[x - 1][0] + self.delete_costs[first_leaf + x - 1];
+        }
+        for y in 1..columns {
+            distances[0][y] = distances[0][y - 1] + self.insert_costs[second_leaf + y - 1];
+        }
+
+        for x in 1..rows {
+            let a = first_leaf + x - 1;
+            for y in 1..columns {
+                let b = second_leaf + y - 1;
+                let deleted = distances[x - 1][y] + self.delete_costs[a];
+                let inserted = distances[x][y - 1] + self.insert_costs[b];
+                if self.first.leftmost_leaves[a] == first_leaf && self.second.leftmost_leaves[b] == second_leaf {
+                    let relabelled = distances[x - 1][y - 1] + (self.relabel_cost)(a, b);
+                    let distance = deleted.min(inserted).min(relabelled);
+                    distances[x][y] = distance;
+                    let second_len = self.second.nodes.len();
+                    self.tree_distances[a * second_len + b] = distance;
+                } else {
+                    let x_before = self.first.leftmost_leaves[a] - first_leaf;
+                    let y_before = self.second.leftmost_leaves[b] - second_leaf;
+                    let replaced = distances[x_before][y_before] + self.tree_distance(a, b);
+                    distances[x][y] = deleted.min(inserted).min(replaced);
+                }
+            }
+        }
+        distances
+    }
+
+    /// Walk back through the forest distances of every pair of subtrees
+    /// involved in the cheapest edit script, collecting the mapped nodes.
+    fn mapping(&mut self) -> Vec<(usize, usize)> {
+        let mut mapping = Vec::new();
+        let mut pending = vec![(self.first.nodes.len() - 1, self.second.nodes.len() - 1)];
+
+        while let Some((i, j)) = pending.pop() {
+            let distances = self.forest_distances(i, j);
+            let first_leaf = self.first.leftmost_leaves[i];
+            let second_leaf = self.second.leftmost_leaves[j];
+            let mut x = i - first_leaf + 1;
+            let mut y = j - second_leaf + 1;
+
+            while x > 0 || y > 0 {
+                if x > 0 && distances[x][y] == distances[x - 1][y] + self.delete_costs[first_leaf + x - 1] {
+                    x -= 1;
+                } else if y > 0 && distances[x][y] == distances[x][y - 1] + self.insert_costs[second_leaf + y - 1] {
+                    y -= 1;
+                } else {
+                    let a = first_leaf + x - 1;
+                    let b = second_leaf + y - 1;
+                    if self.first.leftmost_leaves[a] == first_leaf && self.second.leftmost_leaves[b] == second_leaf {
+                        mapping.push((a, b));
+                        x -= 1;
+                        y -= 1;
+                    } else {
+                        pending.push((a, b));
+                        x = self.first.leftmost_leaves[a] - first_leaf;
+                        y = self.second.leftmost_leaves[b] - second_leaf;
+                    }
+                }
+            }
+        }
+
+        mapping.sort_unstable();
+        mapping
+    }
+}
+
+impl<T> VecTree<T> {
+    /// Compute the edit distance between the subtree rooted at `node_id` and the
+    /// subtree of `other` rooted at `other_node_id`, along with the mapping between
+    /// their nodes that achieves it.
+    ///
+    /// The costs of inserting a node of `other`, of deleting a node of this tree,
+    /// and of relabelling a node of this tree as a node of `other` are given by the
+    /// closures; they should not be negative, and relabelling equal nodes should
+    /// usually cost nothing.
+    ///
+    /// This takes `O(n * m)` memory and, in the worst case, `O(n² * m²)` time,
+    /// for subtrees of `n` and `m` nodes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the nodes is not in its tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate vec_tree;
+    /// # fn main() {
+    /// let tree = tree!["f" => ["d" => ["a", "c" => ["b"]], "e"]];
+    /// let other = tree!["f" => ["c" => ["d" => ["a", "b"]], "e"]];
+    ///
+    /// let result = tree.edit_distance(
+    ///     tree.get_root_index().unwrap(),
+    ///     &other,
+    ///     other.get_root_index().unwrap(),
+    ///     |_| 1.0,
+    ///     |_| 1.0,
+    ///     |a, b| if a == b { 0.0 } else { 1.0 },
+    /// );
+    ///
+    /// // Delete "c" under "d", then insert a "c" above "d".
+    /// assert_eq!(result.distance, 2.0);
+    /// assert_eq!(result.mapping.len(), 5);
+    /// # }
+    /// ```
+    pub fn edit_distance<U, I, D, R>(
+        &self,
+        node_id: Index,
+        other: &VecTree<U>,
+        other_node_id: Index,
+        insert_cost: I,
+        delete_cost: D,
+        relabel_cost: R,
+    ) -> TreeEditDistance
+    where
+        I: Fn(&U) -> f64,
+        D: Fn(&T) -> f64,
+        R: Fn(&T, &U) -> f64,
+    {
+        let first = PostOrder::new(self, node_id);
+        let second = PostOrder::new(other, other_node_id);
+        let mut solver = Solver {
+            first: &first,
+            second: &second,
+            delete_costs: first.nodes.iter().map(|&node_id| delete_cost(&self[node_id])).collect(),
+            insert_costs: second.nodes.iter().map(|&node_id| insert_cost(&other[node_id])).collect(),
+            relabel_cost: |a: usize, b: usize| relabel_cost(&self[first.nodes[a]], &other[second.nodes[b]]),
+            tree_distances: vec![0.0; first.nodes.len() * second.nodes.len()],
+        };
+
+        for &i in &first.keyroots() {
+            for &j in &second.keyroots() {
+                solver.forest_distances(i, j);
+            }
+        }
+
+        let distance = solver.tree_distance(first.nodes.len() - 1, second.nodes.len() - 1);
+        let mapping = solver
+            .mapping()
+            .into_iter()
+            .map(|(a, b)| (first.nodes[a], second.nodes[b]))
+            .collect();
+        TreeEditDistance { distance, mapping }
+    }
+}
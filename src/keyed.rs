@@ -0,0 +1,217 @@
+//! A tree whose children are addressed by key, as the entries of a directory
+//! or the fields of a JSON object are.
+//!
+//! Every node but the root node has a key, unique among its siblings, and the
+//! children of every node are indexed by key so that finding one takes
+//! constant time. The children still keep their order, as in any `VecTree`.
+
+use super::VecTree;
+use generational_arena::Index;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops;
+
+/// A `VecTree` whose children are addressed by key.
+///
+/// The tree can be read through [`tree`](#method.tree), while changes to its
+/// shape have to go through the methods of the `KeyedVecTree`.
+///
+/// # Examples
+///
+/// ```
+/// use vec_tree::keyed::KeyedVecTree;
+///
+/// let mut tree = KeyedVecTree::new();
+/// let root = tree.insert_root("/").unwrap();
+/// let home = tree.insert_keyed("home", "home", root).unwrap();
+/// let user = tree.insert_keyed("user", "user", home).unwrap();
+///
+/// assert_eq!(tree.child_by_key(root, "home"), Some(home));
+/// assert_eq!(tree.child_by_key(home, "user"), Some(user));
+/// assert_eq!(tree.child_by_key(home, "guest"), None);
+///
+/// // Keys are unique among siblings.
+/// assert_eq!(tree.insert_keyed("home", "other home", root), Err(("home", "other home")));
+/// ```
+#[derive(Clone, Debug)]
+pub struct KeyedVecTree<K, T> {
+    tree: VecTree<T>,
+    keys: HashMap<Index, K>,
+    children_by_key: HashMap<Index, HashMap<K, Index>>,
+}
+
+impl<K, T> Default for KeyedVecTree<K, T> {
+    fn default() -> Self {
+        KeyedVecTree {
+            tree: VecTree::default(),
+            keys: HashMap::new(),
+            children_by_key: HashMap::new(),
+        }
+    }
+}
+
+impl<K, T> KeyedVecTree<K, T>
+where
+    K: Clone + Eq + Hash,
+{
+    /// Constructs a new, empty `KeyedVecTree`.
+    pub fn new() -> KeyedVecTree<K, T> {
+        KeyedVecTree::default()
+    }
+
+    /// Constructs a new, empty `KeyedVecTree` with the specified capacity.
+    pub fn with_capacity(n: usize) -> KeyedVecTree<K, T> {
+        KeyedVecTree {
+            tree: VecTree::with_capacity(n),
+            keys: HashMap::with_capacity(n),
+            children_by_key: HashMap::new(),
+        }
+    }
+
+    /// The underlying tree, to read the nodes and walk through them.
+    pub fn tree(&self) -> &VecTree<T> {
+        &self.tree
+    }
+
+    /// Turn this into the underlying tree, dropping the keys.
+    pub fn into_tree(self) -> VecTree<T> {
+        self.tree
+    }
+
+    /// Insert `data` as the root node, which has no key, and return its index.
+    ///
+    /// Since the previous root node would need a key to become a child of the
+    /// new one, `data` is given back if the tree already has a root node.
+    pub fn insert_root(&mut self, data: T) -> Result<Index, T> {
+        if self.tree.get_root_index().is_some() {
+            return Err(data);
+        }
+        Ok(self.tree.insert_root(data))
+    }
+
+    /// Insert `data` under the key `key` as the last child of `parent_id`, and
+    /// return its index.
+    ///
+    /// If `parent_id` already has a child with this key, the key and data are
+    /// given back and the tree is left untouched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent_id` is not in the tree.
+    pub fn insert_keyed(&mut self, key: K, data: T, parent_id: Index) -> Result<Index, (K, T)> {
+        if !self.tree.contains(parent_id) {
+            panic!("The parent node you are trying to insert under is invalid");
+        }
+        let siblings = self.children_by_key.entry(parent_id).or_default();
+        if siblings.contains_key(&key) {
+            return Err((key, data));
+        }
+
+        let node_id = self.tree.insert(data, parent_id);
+        siblings.insert(key.clone(), node_id);
+        self.keys.insert(node_id, key);
+        Ok(node_id)
+    }
+
+    /// Find the child of `node_id` with the key `key`.
+    pub fn child_by_key<Q>(&self, node_id: Index, key: &Q) -> Option<Index>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq + Hash,
+    {
+        self.children_by_key.get(&node_id)?.get(key).cloned()
+    }
+
+    /// Get the key of the node at index `node_id`, or `None` if it is the root
+    /// node or is not in the tree.
+    pub fn key(&self, node_id: Index) -> Option<&K> {
+        self.keys.get(&node_id)
+    }
+
+    /// Change the key of the node at index `node_id` to `new_key`, returning
+    /// the previous key.
+    ///
+    /// The new key is given back, leaving the tree untouched, if a sibling of
+    /// the node already has it, or if the node is the root node or is not in
+    /// the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::keyed::KeyedVecTree;
+    ///
+    /// let mut tree = KeyedVecTree::new();
+    /// let root = tree.insert_root(0).unwrap();
+    /// let a = tree.insert_keyed("a", 1, root).unwrap();
+    /// tree.insert_keyed("b", 2, root).unwrap();
+    ///
+    /// assert_eq!(tree.rename(a, "b"), Err("b"));
+    /// assert_eq!(tree.rename(a, "c"), Ok("a"));
+    /// assert_eq!(tree.child_by_key(root, "c"), Some(a));
+    /// assert_eq!(tree.child_by_key(root, "a"), None);
+    /// ```
+    pub fn rename(&mut self, node_id: Index, new_key: K) -> Result<K, K> {
+        let parent_id = match self.tree.parent(node_id) {
+            Some(parent_id) => parent_id,
+            None => return Err(new_key),
+        };
+        let siblings = self.children_by_key.get_mut(&parent_id).unwrap();
+        if siblings.get(&new_key) == Some(&node_id) {
+            return Ok(new_key);
+        }
+        if siblings.contains_key(&new_key) {
+            return Err(new_key);
+        }
+
+        let old_key = self.keys.insert(node_id, new_key.clone()).unwrap();
+        siblings.remove(&old_key);
+        siblings.insert(new_key, node_id);
+        Ok(old_key)
+    }
+
+    /// Get a shared reference to the data of the node at index `node_id`, if
+    /// it is in the tree.
+    pub fn get(&self, node_id: Index) -> Option<&T> {
+        self.tree.get(node_id)
+    }
+
+    /// Get an exclusive reference to the data of the node at index `node_id`,
+    /// if it is in the tree.
+    pub fn get_mut(&mut self, node_id: Index) -> Option<&mut T> {
+        self.tree.get_mut(node_id)
+    }
+
+    /// Remove the node at index `node_id` along with its descendants and their
+    /// keys, returning its data, or `None` if it is not in the tree.
+    pub fn remove(&mut self, node_id: Index) -> Option<T> {
+        let parent_opt = self.tree.parent(node_id);
+        let keys = &mut self.keys;
+        let children_by_key = &mut self.children_by_key;
+        let data = self.tree.remove_subtree_with(node_id, |descendant_id, _| {
+            keys.remove(&descendant_id);
+            children_by_key.remove(&descendant_id);
+        })?;
+
+        self.children_by_key.remove(&node_id);
+        if let Some(key) = self.keys.remove(&node_id) {
+            let parent_id = parent_opt.unwrap();
+            self.children_by_key.get_mut(&parent_id).unwrap().remove(&key);
+        }
+        Some(data)
+    }
+}
+
+impl<K, T> ops::Index<Index> for KeyedVecTree<K, T> {
+    type Output = T;
+
+    fn index(&self, index: Index) -> &Self::Output {
+        &self.tree[index]
+    }
+}
+
+impl<K, T> ops::IndexMut<Index> for KeyedVecTree<K, T> {
+    fn index_mut(&mut self, index: Index) -> &mut Self::Output {
+        &mut self.tree[index]
+    }
+}
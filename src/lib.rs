@@ -37,6 +37,7 @@ longer need them, because we end up either
       element at that index is `None` and therefore available for allocation
     * `obj1` attempts to get `obj2` at index `i`, but incorrectly is given
       `obj3`, when instead the get should fail.
+
 By introducing a monotonically increasing generation counter to the collection,
 associating each element in the collection with the generation when it was
 inserted, and getting elements from the collection with the *pair* of index and
@@ -103,11 +104,26 @@ assert_eq!(descendants, [1, 10, 13, 11]);
 #![forbid(unsafe_code)]
 
 extern crate generational_arena;
-use generational_arena::Arena;
 pub use generational_arena::Index;
 
 use core::ops;
-use std::{fmt, mem};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::{error, fmt, iter, mem};
+
+mod arena;
+use arena::Arena;
+mod child_order;
+use child_order::ChildOrder;
+
+pub mod augmented;
+pub mod diff;
+pub mod edit_distance;
+pub mod keyed;
+pub mod newick;
+pub mod selector;
+pub mod sexpr;
 
 /// The `VecTree` allows inserting and removing elements that are referred to by
 /// `Index`.
@@ -117,6 +133,8 @@ use std::{fmt, mem};
 pub struct VecTree<T> {
     nodes: Arena<Node<T>>,
     root_index: Option<Index>,
+    // The nodes whose children are indexed by position.
+    child_orders: HashMap<Index, ChildOrder>,
 }
 
 #[derive(Clone, Debug)]
@@ -129,6 +147,43 @@ struct Node<T> {
     data: T,
 }
 
+impl<T> Node<T> {
+    fn new(data: T) -> Node<T> {
+        Node {
+            parent: None,
+            previous_sibling: None,
+            next_sibling: None,
+            first_child: None,
+            last_child: None,
+            data,
+        }
+    }
+
+    /// The same links, with the data that `f` returns for the data of this node.
+    fn map<U, F: FnOnce(&T) -> U>(&self, f: F) -> Node<U> {
+        Node {
+            parent: self.parent,
+            previous_sibling: self.previous_sibling,
+            next_sibling: self.next_sibling,
+            first_child: self.first_child,
+            last_child: self.last_child,
+            data: f(&self.data),
+        }
+    }
+
+    /// The same links, with the data that `f` returns for the data of this node.
+    fn into_map<U, F: FnOnce(T) -> U>(self, f: F) -> Node<U> {
+        Node {
+            parent: self.parent,
+            previous_sibling: self.previous_sibling,
+            next_sibling: self.next_sibling,
+            first_child: self.first_child,
+            last_child: self.last_child,
+            data: f(self.data),
+        }
+    }
+}
+
 const DEFAULT_CAPACITY: usize = 4;
 
 impl<T> Default for VecTree<T> {
@@ -137,6 +192,195 @@ impl<T> Default for VecTree<T> {
     }
 }
 
+/// The reason a parent list could not be turned into a tree by
+/// [`VecTree::from_parent_list`](./struct.VecTree.html#method.from_parent_list).
+///
+/// Nodes are referred to by their position in the list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FromParentListError {
+    /// No node is without a parent (this includes the empty list).
+    NoRoot,
+    /// At least these two nodes are without a parent.
+    MultipleRoots(usize, usize),
+    /// The parent of a node is past the end of the list.
+    ParentOutOfBounds { node: usize, parent: usize },
+    /// The node can't be reached from the root because it is part of a cycle, or descends from one.
+    Cycle(usize),
+}
+
+impl fmt::Display for FromParentListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FromParentListError::NoRoot => write!(f, "no root node"),
+            FromParentListError::MultipleRoots(first, second) => {
+                write!(f, "nodes {} and {} are both root nodes", first, second)
+            }
+            FromParentListError::ParentOutOfBounds { node, parent } => {
+                write!(f, "node {} has parent {} which is out of bounds", node, parent)
+            }
+            FromParentListError::Cycle(node) => write!(f, "node {} is in a cycle", node),
+        }
+    }
+}
+
+impl error::Error for FromParentListError {}
+
+/// The new indices of the nodes of a tree after
+/// [`VecTree::compact`](./struct.VecTree.html#method.compact).
+///
+/// Indexing it with an old index gives the new one, and panics if the old
+/// index wasn't in the tree.
+#[derive(Clone, Debug, Default)]
+pub struct Remap {
+    new_ids: HashMap<Index, Index>,
+}
+
+impl Remap {
+    /// Get the new index of the node that was at `old_id`, if it was in the tree.
+    pub fn get(&self, old_id: Index) -> Option<Index> {
+        self.new_ids.get(&old_id).cloned()
+    }
+
+    /// The number of nodes that were moved.
+    pub fn len(&self) -> usize {
+        self.new_ids.len()
+    }
+
+    /// Whether there were no nodes to move.
+    pub fn is_empty(&self) -> bool {
+        self.new_ids.is_empty()
+    }
+
+    /// Iterate over the pairs of old and new indices, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Index, Index)> + '_ {
+        self.new_ids.iter().map(|(&old_id, &new_id)| (old_id, new_id))
+    }
+}
+
+impl ops::Index<Index> for Remap {
+    type Output = Index;
+
+    fn index(&self, old_id: Index) -> &Index {
+        &self.new_ids[&old_id]
+    }
+}
+
+/// Where the children of a merged node go among the children of the node it is
+/// merged into, with [`VecTree::merge_with`](./struct.VecTree.html#method.merge_with).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChildPlacement {
+    /// Before the children of the node merged into.
+    Prepend,
+    /// After the children of the node merged into.
+    Append,
+    /// Before the children of the node merged into if the merged node comes
+    /// first in tree order, after them otherwise.
+    DocumentOrder,
+    /// Alternating with the children of the node merged into, starting with
+    /// the children of the node which comes first in tree order.
+    Interleave,
+}
+
+/// How [`VecTree::merge_with`](./struct.VecTree.html#method.merge_with) merges two nodes.
+///
+/// The default options are the ones used by `merge`: children placed in tree
+/// order, and parents left with a single child collapsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MergeOptions {
+    /// Where the children of the merged node go.
+    pub placement: ChildPlacement,
+    /// Whether the parent of the merged node is replaced by its last child if
+    /// it is left with only one.
+    pub collapse: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            placement: ChildPlacement::DocumentOrder,
+            collapse: true,
+        }
+    }
+}
+
+/// What became of the nodes merged by [`VecTree::merge`](./struct.VecTree.html#method.merge)
+/// or [`VecTree::merge_with`](./struct.VecTree.html#method.merge_with).
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeOutcome<T, R = T> {
+    /// The index of the node that was merged into, which is still in the tree.
+    pub surviving: Index,
+    /// The data of the merged node, or what the `combine` closure of `merge_with` returned.
+    pub removed: R,
+    /// The data of the parent of the merged node, if it was collapsed.
+    pub removed_parent: Option<T>,
+    /// The index of the only child left to the collapsed parent, which took its
+    /// place. When both nodes had the same parent, this is `surviving`.
+    pub lifted: Option<Index>,
+    /// Whether the root node changed, because the collapsed parent was the root node.
+    pub root_changed: bool,
+}
+
+/// Statistics about the memory used by a tree, as returned by
+/// [`VecTree::memory_stats`](./struct.VecTree.html#method.memory_stats).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryStats {
+    /// The number of nodes in the tree.
+    pub live_nodes: usize,
+    /// The number of allocated slots not holding a node.
+    pub free_slots: usize,
+    /// The size of a node in the arena, links to other nodes included.
+    pub bytes_per_node: usize,
+    /// The part of the allocated slots not holding a node, from `0.0` to `1.0`.
+    pub fragmentation: f64,
+}
+
+/// Build a `VecTree` from a literal.
+///
+/// Each node is an expression, optionally followed by `=>` and the list of its
+/// children between square brackets.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate vec_tree;
+///
+/// # fn main() {
+/// let tree = tree![1 => [2 => [4, 5], 3]];
+///
+/// let root = tree.get_root_index().unwrap();
+/// let values = tree.descendants(root).map(|node| tree[node]).collect::<Vec<_>>();
+/// assert_eq!(values, [1, 2, 4, 5, 3]);
+///
+/// let empty: vec_tree::VecTree<i32> = tree![];
+/// assert_eq!(empty.get_root_index(), None);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! tree {
+    (@children $tree:ident, $parent:ident, ) => {};
+    (@children $tree:ident, $parent:ident, $data:expr => [$($children:tt)*] $(, $($rest:tt)*)?) => {
+        {
+            let node = $tree.insert($data, $parent);
+            $crate::tree!(@children $tree, node, $($children)*);
+        }
+        $crate::tree!(@children $tree, $parent, $($($rest)*)?);
+    };
+    (@children $tree:ident, $parent:ident, $data:expr $(, $($rest:tt)*)?) => {
+        $tree.insert($data, $parent);
+        $crate::tree!(@children $tree, $parent, $($($rest)*)?);
+    };
+    () => {
+        $crate::VecTree::new()
+    };
+    ($root:expr $(=> [$($children:tt)*])? $(,)?) => {{
+        let mut tree = $crate::VecTree::new();
+        let _root = tree.insert_root($root);
+        $($crate::tree!(@children tree, _root, $($children)*);)?
+        tree
+    }};
+}
+
 impl<T> VecTree<T> {
     /// Constructs a new, empty `VecTree`.
     ///
@@ -176,6 +420,7 @@ impl<T> VecTree<T> {
         VecTree {
             nodes: Arena::with_capacity(n),
             root_index: None,
+            child_orders: HashMap::new(),
         }
     }
 
@@ -347,35 +592,148 @@ impl<T> VecTree<T> {
         node_id
     }
 
-    #[inline]
-    fn try_create_node(&mut self, data: T) -> Result<Index, T> {
-        let new_node = Node {
-            parent: None,
-            first_child: None,
-            last_child: None,
-            previous_sibling: None,
-            next_sibling: None,
-            data,
-        };
+    /// Build a tree from nested data, starting with `root`.
+    ///
+    /// `f` splits every element of the nested data into the data of the node
+    /// and the elements of its children, which are added in iteration order.
+    /// The tree is built without recursion, so deeply nested data won't
+    /// overflow the stack.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// struct Item {
+    ///     name: &'static str,
+    ///     items: Vec<Item>,
+    /// }
+    ///
+    /// let item = |name, items| Item { name, items };
+    /// let nested = item("a", vec![item("b", vec![item("d", vec![])]), item("c", vec![])]);
+    ///
+    /// let tree = VecTree::from_nested(nested, |item| (item.name, item.items));
+    ///
+    /// let root = tree.get_root_index().unwrap();
+    /// let names = tree.descendants(root).map(|node| tree[node]).collect::<Vec<_>>();
+    /// assert_eq!(names, ["a", "b", "d", "c"]);
+    /// ```
+    pub fn from_nested<S, F, I>(root: S, mut f: F) -> VecTree<T>
+    where
+        F: FnMut(S) -> (T, I),
+        I: IntoIterator<Item = S>,
+    {
+        let mut tree = VecTree::new();
+        let (data, children) = f(root);
+        let root_id = tree.insert_root(data);
+
+        // The nodes being filled in, with their children still to add.
+        let mut stack = vec![(root_id, children.into_iter())];
+        while let Some((parent_id, children)) = stack.last_mut() {
+            let parent_id = *parent_id;
+            match children.next() {
+                Some(child) => {
+                    let (data, grandchildren) = f(child);
+                    let node_id = tree.insert(data, parent_id);
+                    stack.push((node_id, grandchildren.into_iter()));
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+
+        tree
+    }
+
+    /// Build a tree from a list of nodes, each given with the position of its
+    /// parent in the list.
+    ///
+    /// Exactly one node must be without a parent, and every other node must
+    /// be reachable from it. The children of a node keep their order in the
+    /// list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::{FromParentListError, VecTree};
+    ///
+    /// let tree = VecTree::from_parent_list(vec![(Some(2), "b"), (Some(2), "c"), (None, "a")]).unwrap();
+    ///
+    /// let root = tree.get_root_index().unwrap();
+    /// let names = tree.descendants(root).map(|node| tree[node]).collect::<Vec<_>>();
+    /// assert_eq!(names, ["a", "b", "c"]);
+    ///
+    /// let error = VecTree::from_parent_list(vec![(None, "a"), (Some(2), "b"), (Some(1), "c")]).unwrap_err();
+    /// assert_eq!(error, FromParentListError::Cycle(1));
+    /// ```
+    pub fn from_parent_list<I>(list: I) -> Result<VecTree<T>, FromParentListError>
+    where
+        I: IntoIterator<Item = (Option<usize>, T)>,
+    {
+        let mut parents = Vec::new();
+        let mut data = Vec::new();
+        for (parent, node_data) in list {
+            parents.push(parent);
+            data.push(Some(node_data));
+        }
+
+        let mut root = None;
+        let mut children = vec![Vec::new(); parents.len()];
+        for (node, &parent) in parents.iter().enumerate() {
+            match parent {
+                None => {
+                    if let Some(first_root) = root {
+                        return Err(FromParentListError::MultipleRoots(first_root, node));
+                    }
+                    root = Some(node);
+                }
+                Some(parent) if parent >= parents.len() => {
+                    return Err(FromParentListError::ParentOutOfBounds { node, parent });
+                }
+                Some(parent) => children[parent].push(node),
+            }
+        }
+        let root = root.ok_or(FromParentListError::NoRoot)?;
+
+        let mut tree = VecTree::with_capacity(parents.len());
+        let root_id = tree.insert_root(data[root].take().unwrap());
+        let mut stack = vec![(root_id, children[root].iter())];
+        while let Some((parent_id, child_nodes)) = stack.last_mut() {
+            let parent_id = *parent_id;
+            match child_nodes.next() {
+                Some(&child) => {
+                    let node_id = tree.insert(data[child].take().unwrap(), parent_id);
+                    stack.push((node_id, children[child].iter()));
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
 
-        match self.nodes.try_insert(new_node) {
-            Ok(index) => Ok(index),
-            Err(Node { data, .. }) => Err(data),
+        // Whatever wasn't reached from the root hangs from a cycle.
+        match data.iter().position(Option::is_some) {
+            Some(node) => Err(FromParentListError::Cycle(node)),
+            None => Ok(tree),
         }
     }
 
+    #[inline]
+    fn try_create_node(&mut self, data: T) -> Result<Index, T> {
+        self.nodes.try_insert(Node::new(data)).map_err(|node| node.data)
+    }
+
     #[inline]
     fn create_node(&mut self, data: T) -> Index {
-        let new_node = Node {
-            parent: None,
-            first_child: None,
-            last_child: None,
-            previous_sibling: None,
-            next_sibling: None,
-            data,
-        };
+        self.nodes.insert(Node::new(data))
+    }
 
-        self.nodes.insert(new_node)
+    /// Remove the node at index `node_id` from the arena, without unlinking it.
+    #[inline]
+    fn remove_node(&mut self, node_id: Index) -> Option<Node<T>> {
+        self.child_orders.remove(&node_id);
+        self.nodes.remove(node_id)
     }
 
     /// Remove the element at index `node_id` from the tree.
@@ -395,310 +753,988 @@ impl<T> VecTree<T> {
     /// assert_eq!(tree.remove(root), None);
     /// ```
     pub fn remove(&mut self, node_id: Index) -> Option<T> {
+        self.remove_subtree_with(node_id, |_, _| {})
+    }
+
+    /// Remove the element at index `node_id` from the tree, handing the index
+    /// and data of each of its descendants to `f` as they are removed.
+    ///
+    /// The descendants are removed in post-order, so `f` sees the children of
+    /// a node before the node itself. No memory is allocated along the way.
+    ///
+    /// If the element at index `node_id` is still in the tree, then it is
+    /// returned. If it is not in the tree, then `None` is returned and `f` is
+    /// never called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(1);
+    /// let child = tree.insert(2, root);
+    /// tree.insert(3, child);
+    /// tree.insert(4, child);
+    /// tree.insert(5, root);
+    ///
+    /// let mut removed = Vec::new();
+    /// assert_eq!(tree.remove_subtree_with(child, |_, data| removed.push(data)), Some(2));
+    /// assert_eq!(removed, [3, 4]);
+    /// assert_eq!(tree.len(), 2);
+    /// ```
+    pub fn remove_subtree_with<F>(&mut self, node_id: Index, mut f: F) -> Option<T>
+    where
+        F: FnMut(Index, T),
+    {
         if !self.contains(node_id) {
             return None;
         }
 
-        let descendants = self.descendants(node_id).skip(1).collect::<Vec<Index>>();
-        let node = self.nodes.remove(node_id).unwrap();
-
-        let previous_sibling_opt = node.previous_sibling;
-        let next_sibling_opt = node.next_sibling;
+        self.detach(node_id);
 
-        if let Some(previous_sibling_idx) = previous_sibling_opt {
-            if let Some(next_sibling_idx) = next_sibling_opt {
-                // If has previous and next.
-                let (previous_sibling, next_sibling) =
-                    self.nodes.get2_mut(previous_sibling_idx, next_sibling_idx);
-
-                previous_sibling.unwrap().next_sibling = Some(next_sibling_idx);
-                next_sibling.unwrap().previous_sibling = Some(previous_sibling_idx);
-            } else if let Some(parent_idx) = node.parent {
-                // If has previous but no next.
-                let previous_sibling = &mut self.nodes[previous_sibling_idx];
-                previous_sibling.next_sibling = None;
-
-                let parent = &mut self.nodes[parent_idx];
-                parent.last_child = Some(previous_sibling_idx);
+        // Every removed node is the first child of its parent, so the next node
+        // to remove is the leftmost leaf below its next sibling, or its parent
+        // once it has no children left.
+        let mut current = self.nodes[node_id].first_child;
+        while let Some(mut leaf_id) = current {
+            while let Some(child_id) = self.nodes[leaf_id].first_child {
+                leaf_id = child_id;
             }
-        } else if let Some(next_sibling_idx) = next_sibling_opt {
-            // If has next but no previous.
-            let next_sibling = &mut self.nodes[next_sibling_idx];
-            next_sibling.previous_sibling = None;
-
-            if let Some(parent_idx) = node.parent {
-                let parent = &mut self.nodes[parent_idx];
-                parent.first_child = Some(next_sibling_idx);
+
+            let leaf = self.remove_node(leaf_id).unwrap();
+            let parent_id = leaf.parent.unwrap();
+            let parent = &mut self.nodes[parent_id];
+            parent.first_child = leaf.next_sibling;
+            if leaf.next_sibling.is_none() {
+                parent.last_child = None;
             }
-        } else if let Some(parent_idx) = node.parent {
-            // If it has no previous and no next.
-            let parent = &mut self.nodes[parent_idx];
-            parent.first_child = None;
-            parent.last_child = None;
+
+            current = match leaf.next_sibling {
+                Some(next_sibling_id) => Some(next_sibling_id),
+                None if parent_id == node_id => None,
+                None => Some(parent_id),
+            };
+            f(leaf_id, leaf.data);
         }
 
-        // Remove descendants from arena.
-        for node_id in descendants {
-            self.nodes.remove(node_id);
+        if self.root_index == Some(node_id) {
+            self.root_index = None;
         }
 
-        // Set root_index to None if needed
-        if let Some(root_index) = self.root_index {
-            if root_index == node_id {
+        self.remove_node(node_id).map(|node| node.data)
+    }
+
+    /// Remove the element at index `node_id` and its descendants from the tree,
+    /// returning an iterator over their data along with their depth below
+    /// `node_id`, in the given order.
+    ///
+    /// The nodes are removed from the tree as soon as this is called, and their
+    /// data is dropped with the iterator if it isn't consumed. If `node_id` is
+    /// not in the tree, the iterator is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::{DrainOrder, VecTree};
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(1);
+    /// let child = tree.insert(2, root);
+    /// tree.insert(3, child);
+    /// tree.insert(4, child);
+    /// tree.insert(5, root);
+    ///
+    /// let drained = tree.drain_subtree(child, DrainOrder::PostOrder).collect::<Vec<_>>();
+    /// assert_eq!(drained, [(1, 3), (1, 4), (0, 2)]);
+    /// assert_eq!(tree.len(), 2);
+    ///
+    /// let drained = tree.drain_subtree(root, DrainOrder::PreOrder).collect::<Vec<_>>();
+    /// assert_eq!(drained, [(0, 1), (1, 5)]);
+    /// assert!(tree.is_empty());
+    /// ```
+    pub fn drain_subtree(&mut self, node_id: Index, order: DrainOrder) -> DrainSubtreeIter<'_, T> {
+        let mut pending = Vec::new();
+        if self.contains(node_id) {
+            self.detach(node_id);
+            if self.root_index == Some(node_id) {
                 self.root_index = None;
             }
+            pending.push((node_id, 0));
+        }
+        DrainSubtreeIter {
+            tree: self,
+            root: node_id,
+            order,
+            pending,
         }
-
-        Some(node.data)
     }
 
-    /// forks the given node into a tree of three nodes:
+    /// Remove the element at index `node_id` and its descendants from the tree,
+    /// and return them as a new tree with the same shape.
     ///
-    /// 1. the new parent node in the place of the given node
-    /// 2. the given node as the first child of the new parent node
-    /// 3. the new sibling as the second child of the new parent node.
+    /// The nodes get new indices in the returned tree. If `node_id` is not in
+    /// the tree, then `None` is returned.
     ///
-    /// If the node to fork happens to be the root node,
-    /// the new parent node will become the new root node.
+    /// # Examples
     ///
-    /// All the children of the given node remain attached to it
-    /// (thus are effectively pushed down a level).
+    /// ```
+    /// use vec_tree::VecTree;
     ///
-    /// The parent node takes over the same spot as the given node,
-    /// taking on the same relationships.
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(1);
+    /// let child = tree.insert(2, root);
+    /// tree.insert(3, child);
+    /// tree.insert(4, child);
     ///
-    /// This will automatically allocate more
-    /// capacity if necessary.
+    /// let subtree = tree.take_subtree(child).unwrap();
+    /// assert_eq!(tree.len(), 1);
     ///
-    /// If the tree doesn't contain the given index,
-    /// this method is a no-op.
+    /// let subtree_root = subtree.get_root_index().unwrap();
+    /// let values = subtree.descendants(subtree_root).map(|node| subtree[node]).collect::<Vec<_>>();
+    /// assert_eq!(values, [2, 3, 4]);
+    /// ```
+    pub fn take_subtree(&mut self, node_id: Index) -> Option<VecTree<T>> {
+        if !self.contains(node_id) {
+            return None;
+        }
+
+        let mut subtree = VecTree::new();
+        self.move_subtree_into(node_id, &mut subtree, None, None);
+        Some(subtree)
+    }
+
+    /// Remove the node at index `node_id` and its descendants from this tree,
+    /// and insert them in `target`, the node being placed as [`place`](#method.place)
+    /// does with `parent_id` and `previous_sibling_id`. Returns its new index.
+    fn move_subtree_into(
+        &mut self,
+        node_id: Index,
+        target: &mut VecTree<T>,
+        parent_id: Option<Index>,
+        previous_sibling_id: Option<Index>,
+    ) -> Index {
+        // The new nodes of the ancestors of the next drained node, by depth.
+        let mut parents: Vec<Index> = Vec::new();
+        for (depth, data) in self.drain_subtree(node_id, DrainOrder::PreOrder) {
+            parents.truncate(depth as usize);
+            let new_node_id = target.create_node(data);
+            match parents.last() {
+                Some(&new_parent_id) => target.append_child(new_parent_id, new_node_id),
+                None => target.place(new_node_id, parent_id, previous_sibling_id),
+            }
+            parents.push(new_node_id);
+        }
+        parents[0]
+    }
+
+    /// Replace the data of the node at index `node_id` with `data`.
     ///
-    /// This method returns the index of the new sibling, since
-    /// that, in effect, is why you are forking the tree.
+    /// Returns the previous data, or `None`, dropping `data`, if the node is not
+    /// in the tree.
     ///
     /// # Examples
     ///
     /// ```
-    /// use vec_tree::{VecTree};
-    /// use generational_arena::Index;
+    /// use vec_tree::VecTree;
     ///
     /// let mut tree = VecTree::new();
-    /// let old_root = tree.insert_root(1);
+    /// let root = tree.insert_root(1);
     ///
-    /// let new_sibling = tree.fork(old_root, 0, 2).unwrap();
+    /// assert_eq!(tree.replace(root, 2), Some(1));
+    /// assert_eq!(tree[root], 2);
+    /// ```
+    pub fn replace(&mut self, node_id: Index, data: T) -> Option<T> {
+        self.get_mut(node_id).map(|old_data| mem::replace(old_data, data))
+    }
+
+    /// Replace the subtree rooted at `node_id` with the whole of `other`, which
+    /// takes its place among its siblings.
     ///
-    /// let new_root = tree.get_root_index().unwrap();
-    /// assert_ne!(old_root, new_root);
+    /// The nodes of `other` get new indices in this tree. Returns the index of
+    /// the node that was the root of `other`, along with the replaced subtree as
+    /// a tree of its own, or gives `other` back if `node_id` is not in the tree
+    /// or `other` is empty.
     ///
-    /// assert_eq!(Some(new_root), tree.parent(old_root));
-    /// assert_eq!(tree.parent(new_sibling), tree.parent(old_root));
+    /// # Examples
     ///
-    /// let siblings = tree.children(new_root).collect::<Vec<Index>>();
-    /// assert_eq!(siblings[0], old_root);
-    /// assert_eq!(siblings[1], new_sibling);
+    /// ```
+    /// use vec_tree::VecTree;
     ///
+    /// let mut tree = VecTree::from_sexpr("(r a (b b1) c)", |atom| atom.to_string()).unwrap();
+    /// let root = tree.get_root_index().unwrap();
+    /// let b = tree.children(root).nth(1).unwrap();
+    /// let other = VecTree::from_sexpr("(x y z)", |atom| atom.to_string()).unwrap();
+    ///
+    /// let (x, old) = tree.replace_subtree(b, other).unwrap();
+    /// assert_eq!(tree[x], "x");
+    /// assert_eq!(tree.to_sexpr(|data| data.clone()), "(r a (x y z) c)");
+    /// assert_eq!(old.to_sexpr(|data| data.clone()), "(b b1)");
     /// ```
-    pub fn fork(&mut self, node_id_to_fork: Index, new_parent_data: T, new_sibling_data: T) -> Option<Index> {
-        if !self.contains(node_id_to_fork) {
-            return None;
-        }
-        // Pull out the node and hang onto its links
-        let (optional_parent_node_id, optional_previous_sibling_id, optional_next_sibling_id) = {
-            let node = &self.nodes[node_id_to_fork];
-            let optional_parent_node_id = node.parent;
-            let optional_previous_sibling_id = node.previous_sibling;
-            let optional_next_sibling_id = node.next_sibling;
-            (optional_parent_node_id, optional_previous_sibling_id, optional_next_sibling_id)
+    // The other tree is given back as is, like the data of a failed `try_insert`.
+    #[allow(clippy::result_large_err)]
+    pub fn replace_subtree(
+        &mut self,
+        node_id: Index,
+        mut other: VecTree<T>,
+    ) -> Result<(Index, VecTree<T>), VecTree<T>> {
+        let other_root_id = match other.root_index {
+            Some(other_root_id) if self.contains(node_id) => other_root_id,
+            _ => return Err(other),
         };
 
-        // Create a new parent node and link it in
-        let new_parent_node = Node {
-            parent: optional_parent_node_id,
-            previous_sibling: optional_previous_sibling_id,
-            next_sibling: optional_next_sibling_id,
-            first_child: Some(node_id_to_fork),
-            last_child: None,
-            data: new_parent_data,
+        let (parent_opt, previous_sibling_opt) = {
+            let node = &self.nodes[node_id];
+            (node.parent, node.previous_sibling)
+        };
+        let old_subtree = self.take_subtree(node_id).unwrap();
+
+        self.reserve(other.nodes.len());
+        let new_node_id = other.move_subtree_into(other_root_id, self, parent_opt, previous_sibling_opt);
+        Ok((new_node_id, old_subtree))
+    }
+
+    /// Remove the element at index `node_id` from the tree, its children taking
+    /// its place among its siblings.
+    ///
+    /// The root node can only be removed this way if it has at most one child,
+    /// which then becomes the root node.
+    ///
+    /// If the element at index `node_id` is removed, then it is returned. If it
+    /// is not in the tree, or is the root node with several children, then
+    /// `None` is returned and the tree is left untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(1);
+    /// let a = tree.insert(2, root);
+    /// let b = tree.insert(3, a);
+    /// let c = tree.insert(4, a);
+    /// let d = tree.insert(5, root);
+    ///
+    /// assert_eq!(tree.remove_and_lift_children(a), Some(2));
+    /// assert_eq!(tree.children(root).collect::<Vec<_>>(), [b, c, d]);
+    ///
+    /// assert_eq!(tree.remove_and_lift_children(root), None);
+    /// ```
+    pub fn remove_and_lift_children(&mut self, node_id: Index) -> Option<T> {
+        let (parent_opt, first_child_opt, last_child_opt) = match self.nodes.get(node_id) {
+            Some(node) => (node.parent, node.first_child, node.last_child),
+            None => return None,
         };
-        let new_parent_node_id = self.nodes.insert(new_parent_node);
-        if let Some(parent_node_id) = optional_parent_node_id {
-            if self.nodes[parent_node_id].first_child == Some(node_id_to_fork) {
-                self.nodes[parent_node_id].first_child = Some(new_parent_node_id)
+
+        if parent_opt.is_none() {
+            if first_child_opt != last_child_opt {
+                return None;
             }
-            if self.nodes[parent_node_id].last_child == Some(node_id_to_fork) {
-                self.nodes[parent_node_id].last_child = Some(node_id_to_fork)
+            if let Some(child_id) = first_child_opt {
+                self.detach(child_id);
             }
+            self.root_index = first_child_opt;
         } else {
-            // No grandparent -- we need to update the root
-            self.root_index = Some(new_parent_node_id)
-        }
-        // Update the uncles and aunts
-        if let Some(previous_sibling_id) = optional_previous_sibling_id {
-            self.nodes[previous_sibling_id].next_sibling = Some(new_parent_node_id);
-        }
-        if let Some(next_sibling_id) = optional_next_sibling_id {
-            self.nodes[next_sibling_id].previous_sibling = Some(new_parent_node_id);
+            let mut previous_id = node_id;
+            let mut child_opt = first_child_opt;
+            while let Some(child_id) = child_opt {
+                child_opt = self.nodes[child_id].next_sibling;
+                self.link_after(previous_id, child_id);
+                previous_id = child_id;
+            }
+            self.detach(node_id);
         }
 
-        // Create a new next sibling node and link it in
-        let new_sibling_node = Node {
-            parent: Some(new_parent_node_id),
-            previous_sibling: Some(node_id_to_fork),
-            next_sibling: None,
-            first_child: None,
-            last_child: None,
-            data: new_sibling_data
-        };
-        let new_next_sibling_node_id = self.nodes.insert(new_sibling_node);
-        self.nodes[new_parent_node_id].last_child = Some(new_next_sibling_node_id);
-
-        // Now link our original node back into the tree at the right spot
-        let mut node = self.nodes.get_mut(node_id_to_fork).unwrap();
-        node.parent = Some(new_parent_node_id);
-        node.previous_sibling = None;
-        node.next_sibling = Some(new_next_sibling_node_id);
-
-        return Some(new_next_sibling_node_id)
+        self.remove_node(node_id).map(|node| node.data)
     }
 
-    /// Merge the two given nodes together
-    /// The two nodes are required to have the same parent.
+    /// Insert a new node holding `data` in place of the siblings from `first_id`
+    /// to `last_id` included, which become its children.
     ///
-    /// All the children of the first node
-    /// become children of the second.
+    /// Wrapping the root node, with `first_id` and `last_id` both being the root
+    /// node, makes the new node the root node.
     ///
-    /// If this leaves one child of the parent,
-    /// the child is automatically merged into the parent.
-    pub fn merge(&mut self, node_id: Index, merge_into_node_id: Index) {
-        // Ensure all the requirements are met
-        if !self.nodes.contains(node_id) || !self.nodes.contains(merge_into_node_id) {
-            return
-        }
-        if self.nodes[node_id].parent != self.nodes[merge_into_node_id].parent {
-            return
+    /// Returns the index of the new node, or `None` if the nodes are not in the
+    /// tree, or `last_id` is not `first_id` or one of its following siblings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(1);
+    /// let a = tree.insert(2, root);
+    /// let b = tree.insert(3, root);
+    /// let c = tree.insert(4, root);
+    /// let d = tree.insert(5, root);
+    ///
+    /// let wrapper = tree.wrap(b, c, 6).unwrap();
+    /// assert_eq!(tree.children(root).collect::<Vec<_>>(), [a, wrapper, d]);
+    /// assert_eq!(tree.children(wrapper).collect::<Vec<_>>(), [b, c]);
+    ///
+    /// assert_eq!(tree.wrap(d, a, 7), None);
+    /// ```
+    pub fn wrap(&mut self, first_id: Index, last_id: Index, data: T) -> Option<Index> {
+        if !self.contains(first_id) || !self.contains(last_id) {
+            return None;
         }
-        // big problems if we try to merge into ourselves.
-        if node_id == merge_into_node_id {
-            return
+        if !self.following_siblings(first_id).any(|node_id| node_id == last_id) {
+            return None;
         }
-        // actually this will never happen as we can't have two
-        // distinct root nodes...
-        if self.nodes[node_id].parent.is_none() {
-            return
+
+        let (parent_opt, previous_sibling_opt) = {
+            let first = &self.nodes[first_id];
+            (first.parent, first.previous_sibling)
+        };
+
+        let new_node_id = self.create_node(data);
+        if parent_opt.is_none() {
+            self.make_root(new_node_id);
+            return Some(new_node_id);
+        }
+        self.place(new_node_id, parent_opt, previous_sibling_opt);
+
+        let mut node_opt = Some(first_id);
+        while let Some(node_id) = node_opt {
+            node_opt = self.nodes[node_id].next_sibling;
+            self.append_child(new_node_id, node_id);
+            if node_id == last_id {
+                break;
+            }
         }
 
-        // Gather the information we need that won't be available later:
-        let parent_id = self.nodes[node_id].parent.unwrap();
-        let siblings:Vec<Index> = self.children(parent_id).collect();
-        let optional_previous_sibling_id = self.nodes[node_id].previous_sibling;
-        let optional_next_sibling_id = self.nodes[node_id].next_sibling;
+        Some(new_node_id)
+    }
 
+    /// forks the given node into a tree of three nodes:
+    ///
+    /// 1. the new parent node in the place of the given node
+    /// 2. the given node as the first child of the new parent node
+    /// 3. the new sibling as the second child of the new parent node.
+    ///
+    /// If the node to fork happens to be the root node,
+    /// the new parent node will become the new root node.
+    ///
+    /// All the children of the given node remain attached to it
+    /// (thus are effectively pushed down a level).
+    ///
+    /// The parent node takes over the same spot as the given node,
+    /// taking on the same relationships.
+    ///
+    /// This will automatically allocate more
+    /// capacity if necessary.
+    ///
+    /// If the tree doesn't contain the given index,
+    /// this method is a no-op.
+    ///
+    /// This method returns the index of the new sibling, since
+    /// that, in effect, is why you are forking the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::{VecTree};
+    /// use generational_arena::Index;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let old_root = tree.insert_root(1);
+    ///
+    /// let new_sibling = tree.fork(old_root, 0, 2).unwrap();
+    ///
+    /// let new_root = tree.get_root_index().unwrap();
+    /// assert_ne!(old_root, new_root);
+    ///
+    /// assert_eq!(Some(new_root), tree.parent(old_root));
+    /// assert_eq!(tree.parent(new_sibling), tree.parent(old_root));
+    ///
+    /// let siblings = tree.children(new_root).collect::<Vec<Index>>();
+    /// assert_eq!(siblings[0], old_root);
+    /// assert_eq!(siblings[1], new_sibling);
+    ///
+    /// ```
+    pub fn fork(&mut self, node_id_to_fork: Index, new_parent_data: T, new_sibling_data: T) -> Option<Index> {
+        self.fork_many(node_id_to_fork, new_parent_data, Some(new_sibling_data), 0)
+            .map(|(_, new_sibling_ids)| new_sibling_ids[0])
+    }
 
-        // Determine the birth order of the two nodes:
-        let (first_sibling_id, second_sibling_id) =
-            if Self::is_before(&siblings, node_id, merge_into_node_id) {
-                (node_id, merge_into_node_id)
-            } else {
-                (merge_into_node_id, node_id)
-            };
-        // Collect all the children of the blended family,
-        // because the tree will be corrupt by the time we need it:
-        let step_children = {
-            let mut children = self.children(first_sibling_id).collect::<Vec<Index>>();
-            children.extend(self.children(second_sibling_id).collect::<Vec<Index>>());
+    /// Forks the given node into a new parent node in its place, whose children
+    /// are the new siblings and the given node, at `position` among them.
+    ///
+    /// If the given node is the root node, the new parent node becomes the root
+    /// node. If `position` is past the new siblings, the given node is placed
+    /// after all of them.
+    ///
+    /// Returns the index of the new parent node and the indices of the new
+    /// siblings, in order, or `None` if the tree doesn't contain the given index.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let node = tree.insert(1, root);
+    ///
+    /// let (new_parent, new_siblings) = tree.fork_many(node, 10, vec![11, 12, 13], 2).unwrap();
+    ///
+    /// assert_eq!(tree.children(root).collect::<Vec<_>>(), [new_parent]);
+    /// let children = tree.children(new_parent).collect::<Vec<_>>();
+    /// assert_eq!(children, [new_siblings[0], new_siblings[1], node, new_siblings[2]]);
+    /// ```
+    pub fn fork_many<I>(
+        &mut self,
+        node_id_to_fork: Index,
+        new_parent_data: T,
+        new_siblings_data: I,
+        position: usize,
+    ) -> Option<(Index, Vec<Index>)>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let new_parent_id = self.wrap(node_id_to_fork, node_id_to_fork, new_parent_data)?;
+
+        let mut new_sibling_ids = Vec::new();
+        for data in new_siblings_data {
+            if new_sibling_ids.len() == position {
+                self.append_child(new_parent_id, node_id_to_fork);
+            }
+            let new_sibling_id = self.create_node(data);
+            self.append_child(new_parent_id, new_sibling_id);
+            new_sibling_ids.push(new_sibling_id);
+        }
+        if new_sibling_ids.len() <= position {
+            self.append_child(new_parent_id, node_id_to_fork);
+        }
+
+        Some((new_parent_id, new_sibling_ids))
+    }
+
+    /// Merge the two given nodes together
+    /// The two nodes are required to have the same parent.
+    ///
+    /// All the children of the first node
+    /// become children of the second.
+    ///
+    /// If this leaves one child of the parent,
+    /// the child is automatically merged into the parent.
+    ///
+    /// Returns what became of the nodes, or `None` if they couldn't be merged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let a = tree.insert(1, root);
+    /// let b = tree.insert(2, root);
+    ///
+    /// let outcome = tree.merge(a, b).unwrap();
+    /// assert_eq!(outcome.surviving, b);
+    /// assert_eq!(outcome.removed, 1);
+    /// assert_eq!(outcome.removed_parent, Some(0));
+    /// assert!(outcome.root_changed);
+    /// assert_eq!(tree.get_root_index(), Some(b));
+    /// ```
+    pub fn merge(&mut self, node_id: Index, merge_into_node_id: Index) -> Option<MergeOutcome<T>> {
+        if !self.nodes.contains(node_id) || !self.nodes.contains(merge_into_node_id) {
+            return None
+        }
+        if self.nodes[node_id].parent != self.nodes[merge_into_node_id].parent {
+            return None
+        }
+        self.merge_with(node_id, merge_into_node_id, MergeOptions::default(), |_, data| data)
+    }
+
+    /// Merge the node at index `node_id` into the node at index `merge_into_node_id`,
+    /// which may be anywhere in the tree, as long as neither node is an ancestor of
+    /// the other.
+    ///
+    /// The children of the merged node become children of the other node, placed
+    /// as given by `options`, and `combine` is called with the data of both nodes
+    /// to update the data of the remaining one; what it returns ends up in the
+    /// `removed` field of the outcome. When `options.collapse` is set and the
+    /// parent of the merged node is left with a single child, that child takes
+    /// the place of its parent, which is removed.
+    ///
+    /// Returns `None`, leaving the tree untouched, if one of the nodes is not in
+    /// the tree, if they are the same node, or if one is an ancestor of the other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::{ChildPlacement, MergeOptions, VecTree};
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let a = tree.insert(1, root);
+    /// let a1 = tree.insert(11, a);
+    /// let b = tree.insert(2, root);
+    /// let b1 = tree.insert(21, b);
+    /// let b2 = tree.insert(22, b);
+    ///
+    /// let options = MergeOptions {
+    ///     placement: ChildPlacement::Append,
+    ///     collapse: false,
+    /// };
+    /// let outcome = tree.merge_with(b1, a, options, |a, b1| *a += b1).unwrap();
+    /// assert_eq!(outcome.surviving, a);
+    /// assert_eq!(outcome.removed_parent, None);
+    /// assert_eq!(outcome.lifted, None);
+    ///
+    /// assert_eq!(tree[a], 22);
+    /// assert_eq!(tree.children(root).collect::<Vec<_>>(), [a, b]);
+    /// assert_eq!(tree.children(a).collect::<Vec<_>>(), [a1]);
+    /// assert_eq!(tree.children(b).collect::<Vec<_>>(), [b2]);
+    /// ```
+    pub fn merge_with<F, R>(
+        &mut self,
+        node_id: Index,
+        merge_into_node_id: Index,
+        options: MergeOptions,
+        combine: F,
+    ) -> Option<MergeOutcome<T, R>>
+    where
+        F: FnOnce(&mut T, T) -> R,
+    {
+        if !self.contains(node_id) || !self.contains(merge_into_node_id) || node_id == merge_into_node_id {
+            return None;
+        }
+        if self.ancestors(node_id).any(|id| id == merge_into_node_id)
+            || self.ancestors(merge_into_node_id).any(|id| id == node_id)
+        {
+            return None;
+        }
+
+        let node_children = self.children(node_id).collect::<Vec<Index>>();
+        let merge_into_children = self.children(merge_into_node_id).collect::<Vec<Index>>();
+        let node_comes_first = self.precedes(node_id, merge_into_node_id);
+        let (first_children, second_children) = match options.placement {
+            ChildPlacement::Prepend => (node_children, merge_into_children),
+            ChildPlacement::Append => (merge_into_children, node_children),
+            ChildPlacement::DocumentOrder | ChildPlacement::Interleave if node_comes_first => {
+                (node_children, merge_into_children)
+            }
+            ChildPlacement::DocumentOrder | ChildPlacement::Interleave => (merge_into_children, node_children),
+        };
+        let children = if options.placement == ChildPlacement::Interleave {
+            let mut children = Vec::with_capacity(first_children.len() + second_children.len());
+            let mut first_iter = first_children.into_iter();
+            let mut second_iter = second_children.into_iter();
+            loop {
+                match (first_iter.next(), second_iter.next()) {
+                    (None, None) => break,
+                    (first, second) => children.extend(first.into_iter().chain(second)),
+                }
+            }
+            children
+        } else {
+            let mut children = first_children;
+            children.extend(second_children);
             children
         };
+        for child_id in children {
+            self.append_child(merge_into_node_id, child_id);
+        }
 
-        // connect the last child of the first sibling to the first child of the second sibling
-        let optional_eldest_first_cousin_id = self.nodes[first_sibling_id].last_child;
-        let optional_youngest_second_cousin_id = self.nodes[second_sibling_id].first_child;
+        // The merged node is not the root node, which is the ancestor of every other node.
+        let parent_id = self.nodes[node_id].parent.unwrap();
+        self.detach(node_id);
+        let data = self.remove_node(node_id).unwrap().data;
+        let removed = combine(&mut self[merge_into_node_id], data);
+
+        let root_id = self.root_index;
+        let mut removed_parent = None;
+        let mut lifted = None;
+        if options.collapse {
+            let parent = &self.nodes[parent_id];
+            if parent.first_child.is_some() && parent.first_child == parent.last_child {
+                lifted = parent.first_child;
+                removed_parent = self.remove_and_lift_children(parent_id);
+            }
+        }
+
+        Some(MergeOutcome {
+            surviving: merge_into_node_id,
+            removed,
+            removed_parent,
+            lifted,
+            root_changed: self.root_index != root_id,
+        })
+    }
 
-        // START OF SECTION WHERE TREE IS CORRUPT:
-        if let Some(eldest_first_cousin_id) = optional_eldest_first_cousin_id {
-            if let Some(youngest_second_cousin_id) = optional_youngest_second_cousin_id {
-                self.nodes[eldest_first_cousin_id].next_sibling = Some(youngest_second_cousin_id);
-                self.nodes[youngest_second_cousin_id].previous_sibling = Some(eldest_first_cousin_id);
+    /// Does the node at index `node_id` come before the node at index `other_node_id`
+    /// in tree order?
+    fn precedes(&self, node_id: Index, other_node_id: Index) -> bool {
+        let mut path = self.ancestors(node_id).collect::<Vec<Index>>();
+        let mut other_path = self.ancestors(other_node_id).collect::<Vec<Index>>();
+        loop {
+            match (path.pop(), other_path.pop()) {
+                (Some(id), Some(other_id)) if id == other_id => {}
+                (Some(id), Some(other_id)) => {
+                    return self.following_siblings(id).any(|sibling_id| sibling_id == other_id)
+                }
+                // One node is an ancestor of the other, or they are the same node.
+                (None, other) => return other.is_some(),
+                (Some(_), None) => return false,
             }
         }
-        // Now go through the children we collected above and adjust the parent index.
-        for &child_id in &step_children {
-            self.nodes[child_id].parent = Some(merge_into_node_id)
+    }
+
+    /// Keep only the nodes for which `f` returns `true`, removing every other
+    /// node along with its descendants.
+    ///
+    /// The nodes are visited in tree order, and `f` is not called for the
+    /// descendants of a removed node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::from_sexpr("(1 (2 3 4) (5 6))", |atom| atom.parse::<i32>().unwrap())
+    ///     .unwrap();
+    ///
+    /// tree.retain(|_, &data| data != 2);
+    /// assert_eq!(tree.to_sexpr(|data| data.to_string()), "(1 (5 6))");
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Index, &T) -> bool,
+    {
+        let node_ids = match self.root_index {
+            Some(root_id) => self.descendants(root_id).collect::<Vec<Index>>(),
+            None => return,
+        };
+        for node_id in node_ids {
+            if self.contains(node_id) && !f(node_id, &self[node_id]) {
+                self.remove(node_id);
+            }
         }
-        // Make sure the new parent has correct first and last node
-        if step_children.len() > 0 {
-            self.nodes[merge_into_node_id].first_child = Some(step_children[0]);
-            self.nodes[merge_into_node_id].last_child = Some(step_children[step_children.len()-1]);
+    }
+
+    /// Keep only the nodes for which `f` returns `true`, the children of every
+    /// other node taking its place among its siblings.
+    ///
+    /// The nodes are visited in post-order, each one exactly once. The root node
+    /// is kept, even if `f` returns `false` for it, when it is left with several
+    /// children, so that the tree keeps a single root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::from_sexpr("(1 (2 3 4) (5 6))", |atom| atom.parse::<i32>().unwrap())
+    ///     .unwrap();
+    ///
+    /// tree.retain_lifting(|_, &data| data != 2);
+    /// assert_eq!(tree.to_sexpr(|data| data.to_string()), "(1 3 4 (5 6))");
+    /// ```
+    pub fn retain_lifting<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Index, &T) -> bool,
+    {
+        let node_ids = match self.root_index {
+            Some(root_id) => self
+                .traverse(root_id)
+                .filter_map(|edge| match edge {
+                    NodeEdge::Start(_) => None,
+                    NodeEdge::End(node_id) => Some(node_id),
+                })
+                .collect::<Vec<Index>>(),
+            None => return,
+        };
+        for node_id in node_ids {
+            if !f(node_id, &self[node_id]) {
+                self.remove_and_lift_children(node_id);
+            }
         }
+    }
 
-        // Now remove the node from its parent
-        if self.nodes[parent_id].first_child == Some(node_id) {
-            self.nodes[parent_id].first_child = self.nodes[node_id].next_sibling;
+    /// Sort the children of the node at index `node_id` with the comparison function
+    /// `compare` on their data.
+    ///
+    /// The sort is stable, and only the links between the children change: their
+    /// indices and their data stay where they are.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let a = tree.insert(3, root);
+    /// let b = tree.insert(1, root);
+    /// let c = tree.insert(2, root);
+    ///
+    /// tree.sort_children_by(root, |x, y| x.cmp(y));
+    /// assert_eq!(tree.children(root).collect::<Vec<_>>(), [b, c, a]);
+    /// ```
+    pub fn sort_children_by<F>(&mut self, node_id: Index, mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut children = self.children(node_id).collect::<Vec<Index>>();
+        children.sort_by(|&a, &b| compare(&self[a], &self[b]));
+        self.relink_children(node_id, &children);
+    }
+
+    /// Sort the children of the node at index `node_id` by the key `f` gives for
+    /// their data.
+    ///
+    /// The sort is stable, and only the links between the children change.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root("");
+    /// let a = tree.insert("ccc", root);
+    /// let b = tree.insert("a", root);
+    /// let c = tree.insert("bb", root);
+    ///
+    /// tree.sort_children_by_key(root, |data| data.len());
+    /// assert_eq!(tree.children(root).collect::<Vec<_>>(), [b, c, a]);
+    /// ```
+    pub fn sort_children_by_key<K, F>(&mut self, node_id: Index, mut f: F)
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.sort_children_by(node_id, |a, b| f(a).cmp(&f(b)));
+    }
+
+    /// Reverse the order of the children of the node at index `node_id`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let a = tree.insert(1, root);
+    /// let b = tree.insert(2, root);
+    /// let c = tree.insert(3, root);
+    ///
+    /// tree.reverse_children(root);
+    /// assert_eq!(tree.children(root).collect::<Vec<_>>(), [c, b, a]);
+    /// ```
+    pub fn reverse_children(&mut self, node_id: Index) {
+        let mut children = self.children(node_id).collect::<Vec<Index>>();
+        children.reverse();
+        self.relink_children(node_id, &children);
+    }
+
+    /// Swap the places of two siblings among the children of their parent.
+    ///
+    /// Returns `false`, leaving the tree untouched, if one of the nodes is not in
+    /// the tree, or if they don't have the same parent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let a = tree.insert(1, root);
+    /// let b = tree.insert(2, root);
+    /// let c = tree.insert(3, root);
+    ///
+    /// assert!(tree.swap_siblings(a, c));
+    /// assert_eq!(tree.children(root).collect::<Vec<_>>(), [c, b, a]);
+    ///
+    /// let c1 = tree.insert(31, c);
+    /// assert!(!tree.swap_siblings(a, c1));
+    /// ```
+    pub fn swap_siblings(&mut self, node_id: Index, other_node_id: Index) -> bool {
+        let parent_opt = match (self.nodes.get(node_id), self.nodes.get(other_node_id)) {
+            (Some(node), Some(other_node)) if node.parent == other_node.parent => node.parent,
+            _ => return false,
+        };
+
+        let parent_id = match parent_opt {
+            Some(parent_id) if node_id != other_node_id => parent_id,
+            _ => return true,
+        };
+
+        // The index of the children, if any, is updated in place rather than
+        // along with each move.
+        let order_opt = self.child_orders.remove(&parent_id);
+        self.exchange_places(node_id, other_node_id);
+        if let Some(mut order) = order_opt {
+            order.swap(node_id, other_node_id);
+            self.child_orders.insert(parent_id, order);
         }
-        if self.nodes[parent_id].last_child == Some(node_id) {
-            self.nodes[parent_id].last_child = self.nodes[node_id].previous_sibling;
+        true
+    }
+
+    /// Swap the places of two nodes in the tree, each taking along its descendants.
+    ///
+    /// The nodes may have different parents, but neither can be an ancestor of
+    /// the other.
+    ///
+    /// Returns `false`, leaving the tree untouched, if one of the nodes is not in
+    /// the tree or is an ancestor of the other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let a = tree.insert(1, root);
+    /// let a1 = tree.insert(11, a);
+    /// let b = tree.insert(2, root);
+    /// let b1 = tree.insert(21, b);
+    ///
+    /// assert!(tree.swap_positions(a1, b));
+    /// assert_eq!(tree.children(root).collect::<Vec<_>>(), [a, a1]);
+    /// assert_eq!(tree.children(a).collect::<Vec<_>>(), [b]);
+    /// assert_eq!(tree.children(b).collect::<Vec<_>>(), [b1]);
+    ///
+    /// assert!(!tree.swap_positions(a, b1));
+    /// ```
+    pub fn swap_positions(&mut self, node_id: Index, other_node_id: Index) -> bool {
+        if !self.contains(node_id) || !self.contains(other_node_id) {
+            return false;
         }
-        // And connect its siblings around it
-        if let Some(previous_sibling_id) = optional_previous_sibling_id {
-            self.nodes[previous_sibling_id].next_sibling = optional_next_sibling_id
+        if node_id == other_node_id {
+            return true;
         }
-        if let Some(next_sibling_id) = optional_next_sibling_id {
-            self.nodes[next_sibling_id].previous_sibling = optional_previous_sibling_id
+        if self.ancestors(node_id).any(|id| id == other_node_id)
+            || self.ancestors(other_node_id).any(|id| id == node_id)
+        {
+            return false;
         }
-        // END OF SECTION WHERE TREE IS CORRUPT (although it may now have a parent with one child)
 
-        // Merge the sibling and the parent if the sibling is now an only child
-        if siblings.len() == 2 {
-            let optional_grandparent_id = self.nodes[parent_id].parent;
-            if let Some(grandparent_id) = optional_grandparent_id {
-                // grab the parent's siblings now, because the tree will be corrupt
-                // by the time we need it.
-                let optional_previous_uncle_id = self.nodes[parent_id].previous_sibling;
-                let optional_next_uncle_id = self.nodes[parent_id].next_sibling;
+        // Neither node is the root node, which is the ancestor of every other node.
+        self.exchange_places(node_id, other_node_id);
+        true
+    }
 
-                // START OF SECTION WHERE TREE IS CORRUPT:
-                if self.nodes[grandparent_id].first_child == Some(parent_id) {
-                    self.nodes[grandparent_id].first_child = Some(merge_into_node_id);
-                }
-                if self.nodes[grandparent_id].last_child == Some(parent_id) {
-                    self.nodes[grandparent_id].last_child = Some(merge_into_node_id);
-                }
-                self.nodes[merge_into_node_id].parent = Some(grandparent_id);
+    /// Move each of two different nodes, neither being the root node or an
+    /// ancestor of the other, to the place of the other.
+    fn exchange_places(&mut self, node_id: Index, other_node_id: Index) {
+        let (parent_id, previous_sibling_opt) = {
+            let node = &self.nodes[node_id];
+            (node.parent, node.previous_sibling)
+        };
+        let (other_parent_id, other_previous_sibling_opt) = {
+            let other_node = &self.nodes[other_node_id];
+            (other_node.parent, other_node.previous_sibling)
+        };
 
-                if let Some(previous_uncle_id) = optional_previous_uncle_id {
-                    self.nodes[previous_uncle_id].next_sibling = Some(merge_into_node_id);
-                    self.nodes[merge_into_node_id].previous_sibling = Some(previous_uncle_id);
-                }
-                if let Some(next_uncle_id) = optional_next_uncle_id {
-                    self.nodes[next_uncle_id].previous_sibling = Some(merge_into_node_id);
-                    self.nodes[merge_into_node_id].next_sibling = Some(next_uncle_id);
-                }
-                // END OF SECTION WHERE TREE IS CORRUPT
-            } else {
-                self.root_index = Some(merge_into_node_id);
-                self.nodes[merge_into_node_id].parent = None;
+        // Adjacent siblings just need the second one moved before the first one.
+        if other_previous_sibling_opt == Some(node_id) {
+            self.place(other_node_id, parent_id, previous_sibling_opt);
+        } else if previous_sibling_opt == Some(other_node_id) {
+            self.place(node_id, other_parent_id, other_previous_sibling_opt);
+        } else {
+            self.place(node_id, other_parent_id, other_previous_sibling_opt);
+            self.place(other_node_id, parent_id, previous_sibling_opt);
+        }
+    }
+
+    /// Swap the data of two nodes, leaving the nodes where they are.
+    ///
+    /// Returns `false`, leaving the tree untouched, if one of the nodes is not in
+    /// the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let a = tree.insert(1, root);
+    ///
+    /// assert!(tree.swap_data(root, a));
+    /// assert_eq!(tree[root], 1);
+    /// assert_eq!(tree[a], 0);
+    /// assert_eq!(tree.parent(a), Some(root));
+    /// ```
+    pub fn swap_data(&mut self, node_id: Index, other_node_id: Index) -> bool {
+        if node_id == other_node_id {
+            return self.contains(node_id);
+        }
+        match self.nodes.get2_mut(node_id, other_node_id) {
+            (Some(node), Some(other_node)) => {
+                mem::swap(&mut node.data, &mut other_node.data);
+                true
             }
-            self.nodes.remove(parent_id);
+            _ => false,
+        }
+    }
+
+    /// Sort the children of the node at index `node_id`, and those of all its
+    /// descendants, with the comparison function `compare` on their data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::from_sexpr("(0 (3 5 4) 1 (2 7 6))", |atom| atom.parse::<i32>().unwrap()).unwrap();
+    /// let root = tree.get_root_index().unwrap();
+    ///
+    /// tree.sort_subtree_by(root, |x, y| x.cmp(y));
+    /// assert_eq!(tree.to_sexpr(|data| data.to_string()), "(0 1 (2 6 7) (3 4 5))");
+    /// ```
+    pub fn sort_subtree_by<F>(&mut self, node_id: Index, mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let parents = self
+            .descendants(node_id)
+            .filter(|&id| self.has_children(id))
+            .collect::<Vec<Index>>();
+        for parent_id in parents {
+            self.sort_children_by(parent_id, &mut compare);
         }
-        self.nodes.remove(node_id);
     }
 
-    fn is_before(vector:&Vec<Index>, index_1: Index, index_2: Index) -> bool {
-        let mut is_index_1_found = false;
-        let mut is_index_2_found = false;
-        for &index in vector {
-            if index == index_1 {
-                if is_index_2_found {
-                    return false
-                }
-                is_index_1_found = true
-            } else if index == index_2 {
-                if is_index_1_found {
-                    return true
-                }
-                is_index_2_found = true
-            }
+    /// Link the given nodes, which must be all the children of the node at index
+    /// `node_id`, as its children in this order.
+    fn relink_children(&mut self, node_id: Index, children: &[Index]) {
+        let parent = &mut self.nodes[node_id];
+        parent.first_child = children.first().cloned();
+        parent.last_child = children.last().cloned();
+        for (position, &child_id) in children.iter().enumerate() {
+            let child = &mut self.nodes[child_id];
+            child.previous_sibling = position.checked_sub(1).map(|previous| children[previous]);
+            child.next_sibling = children.get(position + 1).cloned();
+        }
+        if let Some(order) = self.child_orders.get_mut(&node_id) {
+            *order = ChildOrder::new(children.iter().cloned());
         }
-        false // this indicates at least one of the two indices were not in the list
     }
 
     /// Is the element at index `node_id` in the tree?
@@ -742,7 +1778,7 @@ impl<T> VecTree<T> {
 
             new_child_node.parent = Some(node_id);
 
-            last_child_opt = mem::replace(&mut node.last_child, Some(new_child_id));
+            last_child_opt = node.last_child.replace(new_child_id);
             if let Some(last_child) = last_child_opt {
                 new_child_node.previous_sibling = Some(last_child);
             } else {
@@ -755,6 +1791,83 @@ impl<T> VecTree<T> {
             debug_assert!(self.nodes[last_child].next_sibling.is_none());
             self.nodes[last_child].next_sibling = Some(new_child_id);
         }
+
+        if let Some(order) = self.child_orders.get_mut(&node_id) {
+            let len = order.len();
+            order.insert(len, new_child_id);
+        }
+    }
+
+    /// Move `new_child_id` to be the first child of `node_id`, without checking
+    /// that it isn't an ancestor of `node_id`.
+    #[inline]
+    fn link_first_child(&mut self, node_id: Index, new_child_id: Index) {
+        self.detach(new_child_id);
+
+        let first_child_opt;
+        {
+            let (node_opt, new_child_node_opt) = self.nodes.get2_mut(node_id, new_child_id);
+
+            if node_opt.is_none() {
+                panic!("The node you are trying to prepend to is invalid");
+            }
+
+            if new_child_node_opt.is_none() {
+                panic!("The node you are trying to prepend is invalid");
+            }
+
+            let node = node_opt.unwrap();
+            let new_child_node = new_child_node_opt.unwrap();
+
+            new_child_node.parent = Some(node_id);
+
+            first_child_opt = node.first_child.replace(new_child_id);
+            if let Some(first_child) = first_child_opt {
+                new_child_node.next_sibling = Some(first_child);
+            } else {
+                debug_assert!(node.last_child.is_none());
+                node.last_child = Some(new_child_id);
+            }
+        }
+
+        if let Some(first_child) = first_child_opt {
+            debug_assert!(self.nodes[first_child].previous_sibling.is_none());
+            self.nodes[first_child].previous_sibling = Some(new_child_id);
+        }
+
+        if let Some(order) = self.child_orders.get_mut(&node_id) {
+            order.insert(0, new_child_id);
+        }
+    }
+
+    /// Move `new_sibling_id` to be the next sibling of `sibling_id`, without
+    /// checking that it isn't an ancestor of `sibling_id`.
+    fn link_after(&mut self, sibling_id: Index, new_sibling_id: Index) {
+        self.detach(new_sibling_id);
+
+        let (parent_id, next_sibling_opt) = match self.nodes.get(sibling_id) {
+            Some(Node { parent: Some(parent_id), next_sibling, .. }) => (*parent_id, *next_sibling),
+            Some(_) => panic!("The root node can't have siblings"),
+            None => panic!("The node you are trying to insert after is invalid"),
+        };
+
+        {
+            let new_sibling_node = &mut self.nodes[new_sibling_id];
+            new_sibling_node.parent = Some(parent_id);
+            new_sibling_node.previous_sibling = Some(sibling_id);
+            new_sibling_node.next_sibling = next_sibling_opt;
+        }
+
+        self.nodes[sibling_id].next_sibling = Some(new_sibling_id);
+        match next_sibling_opt {
+            Some(next_sibling_id) => self.nodes[next_sibling_id].previous_sibling = Some(new_sibling_id),
+            None => self.nodes[parent_id].last_child = Some(new_sibling_id),
+        }
+
+        if let Some(order) = self.child_orders.get_mut(&parent_id) {
+            let position = order.position(sibling_id).unwrap();
+            order.insert(position + 1, new_sibling_id);
+        }
     }
 
     #[inline]
@@ -773,18 +1886,333 @@ impl<T> VecTree<T> {
         } else if let Some(parent) = parent {
             self.nodes[parent].last_child = previous_sibling;
         }
-
-        if let Some(previous_sibling) = previous_sibling {
-            self.nodes[previous_sibling].next_sibling = next_sibling;
-        } else if let Some(parent) = parent {
-            self.nodes[parent].first_child = next_sibling;
+
+        if let Some(previous_sibling) = previous_sibling {
+            self.nodes[previous_sibling].next_sibling = next_sibling;
+        } else if let Some(parent) = parent {
+            self.nodes[parent].first_child = next_sibling;
+        }
+
+        if let Some(order) = parent.and_then(|parent| self.child_orders.get_mut(&parent)) {
+            order.remove(node_id);
+        }
+    }
+
+    /// Make `node_id` the root node, the current root becoming its last child.
+    fn make_root(&mut self, node_id: Index) {
+        if self.root_index == Some(node_id) {
+            return;
+        }
+        self.detach(node_id);
+        if let Some(old_root_id) = self.root_index.replace(node_id) {
+            self.append_child(node_id, old_root_id);
+        }
+    }
+
+    /// Move `node_id` right after `previous_sibling_id` among the children of `parent_id`,
+    /// or to be the first child when there is no previous sibling.
+    /// Without a parent, the node becomes the root.
+    fn place(&mut self, node_id: Index, parent_id: Option<Index>, previous_sibling_id: Option<Index>) {
+        match (parent_id, previous_sibling_id) {
+            (None, _) => self.make_root(node_id),
+            (Some(parent_id), None) => self.link_first_child(parent_id, node_id),
+            (Some(_), Some(previous_sibling_id)) => self.link_after(previous_sibling_id, node_id),
+        }
+    }
+
+    /// Get a shared reference to the element at index `node_id` if it is in the
+    /// tree.
+    ///
+    /// If the element at index `node_id` is not in the tree, then `None` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(42);
+    ///
+    /// assert_eq!(tree.get(root), Some(&42));
+    /// tree.remove(root);
+    /// assert!(tree.get(root).is_none());
+    /// ```
+    pub fn get(&self, node_id: Index) -> Option<&T> {
+        match self.nodes.get(node_id) {
+            Some(node) => Some(&node.data),
+            _ => None,
+        }
+    }
+
+    /// Get an exclusive reference to the element at index `node_id` if it is in the
+    /// tree.
+    ///
+    /// If the element at index `node_id` is not in the tree, then `None` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(42);
+    ///
+    /// *tree.get_mut(root).unwrap() += 1;
+    /// assert_eq!(tree.remove(root), Some(43));
+    /// assert!(tree.get_mut(root).is_none());
+    /// ```
+    pub fn get_mut(&mut self, node_id: Index) -> Option<&mut T> {
+        match self.nodes.get_mut(node_id) {
+            Some(node) => Some(&mut node.data),
+            _ => None,
+        }
+    }
+    /// Get the root node index from the tree.
+    ///
+    /// If no root node is created in the tree, None is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// assert_eq!(tree.get_root_index(), None);
+    ///
+    /// tree.insert_root(42);
+    /// let root = tree.get_root_index().unwrap();
+    /// assert_eq!(tree[root], 42);
+    /// ```
+    pub fn get_root_index(&self) -> Option<Index> {
+        self.root_index
+    }
+
+    /// Get the capacity of this tree.
+    ///
+    /// The capacity is the maximum number of elements the tree can hold
+    /// without further allocation, including however many it currently
+    /// contains.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::with_capacity(10);
+    /// let root = tree.insert_root(0);
+    ///
+    /// // `try_insert` does not allocate new capacity.
+    /// for i in 1..10 {
+    ///     assert!(tree.try_insert(i, root).is_ok());
+    ///     assert_eq!(tree.capacity(), 10);
+    /// }
+    ///
+    /// // But `insert` will if the root is already at capacity.
+    /// tree.insert(11, root);
+    /// assert!(tree.capacity() > 10);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    /// Get the number of nodes in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::with_capacity(10);
+    /// assert_eq!(tree.len(), 0);
+    ///
+    /// let root = tree.insert_root(0);
+    /// tree.insert(1, root);
+    /// assert_eq!(tree.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Return whether the tree has no nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// assert!(tree.is_empty());
+    ///
+    /// let root = tree.insert_root(0);
+    /// assert!(!tree.is_empty());
+    ///
+    /// tree.remove(root);
+    /// assert!(tree.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Release the memory of the tree that isn't used.
+    ///
+    /// The slots after the last node of the tree are released. Every node keeps
+    /// its index, and the indices of removed nodes stay invalid. Free slots
+    /// between nodes are kept: to release them as well, use [`compact`](#method.compact), which
+    /// gives the nodes new indices.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::with_capacity(100);
+    /// let root = tree.insert_root(0);
+    /// let child = tree.insert(1, root);
+    /// let grandchild = tree.insert(2, child);
+    ///
+    /// tree.remove(grandchild);
+    /// tree.shrink_to_fit();
+    /// assert_eq!(tree.capacity(), 2);
+    /// assert_eq!(tree[child], 1);
+    ///
+    /// // The index of the removed node doesn't designate the next one.
+    /// let new_grandchild = tree.insert(3, child);
+    /// assert_ne!(grandchild, new_grandchild);
+    /// assert_eq!(tree.get(grandchild), None);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+    }
+
+    /// Get statistics about the memory used by the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::with_capacity(4);
+    /// let root = tree.insert_root(0u64);
+    /// tree.insert(1, root);
+    ///
+    /// let stats = tree.memory_stats();
+    /// assert_eq!(stats.live_nodes, 2);
+    /// assert_eq!(stats.free_slots, 2);
+    /// assert_eq!(stats.fragmentation, 0.5);
+    /// assert!(stats.bytes_per_node > 8);
+    /// ```
+    pub fn memory_stats(&self) -> MemoryStats {
+        let capacity = self.nodes.capacity();
+        let live_nodes = self.nodes.len();
+        let free_slots = capacity - live_nodes;
+        MemoryStats {
+            live_nodes,
+            free_slots,
+            bytes_per_node: mem::size_of::<Node<T>>(),
+            fragmentation: if capacity == 0 {
+                0.0
+            } else {
+                free_slots as f64 / capacity as f64
+            },
+        }
+    }
+
+    /// Clear all the items inside the tree, but keep its allocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::with_capacity(1);
+    /// let root = tree.insert_root(42);
+    /// tree.insert(43, root); // The capacity is doubled when reached.
+    ///
+    /// tree.clear();
+    /// assert_eq!(tree.capacity(), 2);
+    /// ```
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.root_index = None;
+        self.child_orders.clear();
+    }
+
+    /// Store the nodes anew, in tree order and without free slots in between,
+    /// and release the capacity that isn't needed anymore.
+    ///
+    /// Every node gets a new index: the returned `Remap` gives it for every
+    /// old index. Old indices are not valid anymore, as if their nodes had been
+    /// removed, so they can't designate another node by accident.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::with_capacity(100);
+    /// let root = tree.insert_root(0);
+    /// let leaves = (1..100).map(|i| tree.insert(i, root)).collect::<Vec<_>>();
+    /// for &leaf in &leaves[..90] {
+    ///     tree.remove(leaf);
+    /// }
+    ///
+    /// let remap = tree.compact();
+    /// assert_eq!(tree.capacity(), 10);
+    ///
+    /// let root = remap[root];
+    /// let last_leaf = remap[leaves[98]];
+    /// assert_eq!(tree[last_leaf], 99);
+    /// assert_eq!(tree.parent(last_leaf), Some(root));
+    /// assert_eq!(remap.get(leaves[0]), None);
+    /// assert_eq!(tree.get(leaves[98]), None);
+    /// ```
+    pub fn compact(&mut self) -> Remap {
+        let order = match self.root_index {
+            Some(root_id) => self.descendants(root_id).collect::<Vec<Index>>(),
+            None => Vec::new(),
+        };
+        let new_nodes = self.nodes.successor(order.len());
+        let mut old_nodes = mem::replace(&mut self.nodes, new_nodes);
+
+        let mut new_ids = HashMap::with_capacity(order.len());
+        for old_id in order {
+            let node = old_nodes.remove(old_id).unwrap();
+            new_ids.insert(old_id, self.nodes.insert(node));
+        }
+
+        let remap = |link: &mut Option<Index>| {
+            if let Some(old_id) = *link {
+                *link = Some(new_ids[&old_id]);
+            }
+        };
+        for &new_id in new_ids.values() {
+            let node = &mut self.nodes[new_id];
+            remap(&mut node.parent);
+            remap(&mut node.previous_sibling);
+            remap(&mut node.next_sibling);
+            remap(&mut node.first_child);
+            remap(&mut node.last_child);
+        }
+        remap(&mut self.root_index);
+
+        let indexed_parents = mem::take(&mut self.child_orders)
+            .keys()
+            .filter_map(|old_id| new_ids.get(old_id).cloned())
+            .collect::<Vec<Index>>();
+        for parent_id in indexed_parents {
+            let order = ChildOrder::new(self.children(parent_id));
+            self.child_orders.insert(parent_id, order);
         }
+
+        Remap { new_ids }
     }
 
-    /// Get a shared reference to the element at index `node_id` if it is in the
-    /// tree.
+    /// Build a tree of the same shape, whose nodes hold what `f` returns for the
+    /// index and data of each node of this tree.
     ///
-    /// If the element at index `node_id` is not in the tree, then `None` is returned.
+    /// Every node keeps its index in the new tree, so that anything keyed by
+    /// the indices of this tree can be used with the new tree as well. The
+    /// nodes are visited in no particular order.
     ///
     /// # Examples
     ///
@@ -792,23 +2220,30 @@ impl<T> VecTree<T> {
     /// use vec_tree::VecTree;
     ///
     /// let mut tree = VecTree::new();
-    /// let root = tree.insert_root(42);
+    /// let root = tree.insert_root("root");
+    /// let child = tree.insert("child", root);
     ///
-    /// assert_eq!(tree.get(root), Some(&42));
-    /// tree.remove(root);
-    /// assert!(tree.get(root).is_none());
+    /// let lengths = tree.map(|_, data| data.len());
+    /// assert_eq!(lengths[root], 4);
+    /// assert_eq!(lengths[child], 5);
+    /// assert_eq!(lengths.parent(child), Some(root));
     /// ```
-    pub fn get(&self, node_id: Index) -> Option<&T> {
-        match self.nodes.get(node_id) {
-            Some(Node { ref data, .. }) => Some(data),
-            _ => None,
+    pub fn map<U, F>(&self, mut f: F) -> VecTree<U>
+    where
+        F: FnMut(Index, &T) -> U,
+    {
+        VecTree {
+            nodes: self.nodes.map(|node_id, node| node.map(|data| f(node_id, data))),
+            root_index: self.root_index,
+            child_orders: self.child_orders.clone(),
         }
     }
 
-    /// Get an exclusive reference to the element at index `node_id` if it is in the
-    /// tree.
+    /// Turn this tree into a tree of the same shape, whose nodes hold what `f`
+    /// returns for the index and data of each node of this tree.
     ///
-    /// If the element at index `node_id` is not in the tree, then `None` is returned.
+    /// Every node keeps its index in the new tree, as with [`map`](#method.map).
+    /// The nodes are visited in no particular order.
     ///
     /// # Examples
     ///
@@ -816,83 +2251,238 @@ impl<T> VecTree<T> {
     /// use vec_tree::VecTree;
     ///
     /// let mut tree = VecTree::new();
-    /// let root = tree.insert_root(42);
+    /// let root = tree.insert_root(vec![1, 2]);
+    /// let child = tree.insert(vec![3], root);
     ///
-    /// *tree.get_mut(root).unwrap() += 1;
-    /// assert_eq!(tree.remove(root), Some(43));
-    /// assert!(tree.get_mut(root).is_none());
+    /// let sums = tree.into_map(|_, data| data.into_iter().sum::<i32>());
+    /// assert_eq!(sums[root], 3);
+    /// assert_eq!(sums[child], 3);
     /// ```
-    pub fn get_mut(&mut self, node_id: Index) -> Option<&mut T> {
-        match self.nodes.get_mut(node_id) {
-            Some(Node { ref mut data, .. }) => Some(data),
-            _ => None,
+    pub fn into_map<U, F>(self, mut f: F) -> VecTree<U>
+    where
+        F: FnMut(Index, T) -> U,
+    {
+        VecTree {
+            nodes: self.nodes.into_map(|node_id, node| node.into_map(|data| f(node_id, data))),
+            root_index: self.root_index,
+            child_orders: self.child_orders,
         }
     }
-    /// Get the root node index from the tree.
+
+    /// Compute a value for every node of the subtree rooted at `node_id`, from the
+    /// bottom up: `leaf_fn` gives the value of a leaf, and `combine_fn` the value
+    /// of any other node from the values of its children, in order.
     ///
-    /// If no root node is created in the tree, None is returned.
+    /// Returns the values by node index. The subtree is walked without recursion,
+    /// so that deep trees can't overflow the stack.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
     ///
     /// # Examples
     ///
     /// ```
     /// use vec_tree::VecTree;
     ///
-    /// let mut tree = VecTree::new();
-    /// assert_eq!(tree.get_root_index(), None);
+    /// let tree = VecTree::from_sexpr("(1 (2 3 4) 5)", |atom| atom.parse::<i32>().unwrap()).unwrap();
+    /// let root = tree.get_root_index().unwrap();
     ///
-    /// tree.insert_root(42);
+    /// let sums = tree.fold_up(
+    ///     root,
+    ///     |_, &data| data,
+    ///     |_, &data, children| data + children.iter().sum::<i32>(),
+    /// );
+    /// assert_eq!(sums[&root], 15);
+    /// ```
+    pub fn fold_up<A, L, C>(
+        &self,
+        node_id: Index,
+        mut leaf_fn: L,
+        mut combine_fn: C,
+    ) -> HashMap<Index, A>
+    where
+        L: FnMut(Index, &T) -> A,
+        C: FnMut(Index, &T, &[A]) -> A,
+    {
+        let mut values = HashMap::new();
+        // The values of the nodes whose parent hasn't been reached yet, the
+        // values of the children of a node being the last ones when it is.
+        let mut pending: Vec<A> = Vec::new();
+        for edge in self.traverse(node_id) {
+            if let NodeEdge::End(current_id) = edge {
+                let value = if self.has_children(current_id) {
+                    let first_child = pending.len() - self.children(current_id).count();
+                    let value = combine_fn(current_id, &self[current_id], &pending[first_child..]);
+                    let child_values = pending.drain(first_child..);
+                    values.extend(self.children(current_id).zip(child_values));
+                    value
+                } else {
+                    leaf_fn(current_id, &self[current_id])
+                };
+                pending.push(value);
+            }
+        }
+        values.extend(pending.pop().map(|value| (node_id, value)));
+        values
+    }
+
+    /// Compute a value for every node of the subtree rooted at `node_id`, from the
+    /// top down: `step_fn` gives the value of a node from the value of its parent,
+    /// `init` standing for the value of the parent of `node_id`.
+    ///
+    /// Returns the values by node index. The subtree is walked without recursion,
+    /// so that deep trees can't overflow the stack.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let tree = VecTree::from_sexpr("(a (b c) d)", |atom| atom.to_string()).unwrap();
     /// let root = tree.get_root_index().unwrap();
-    /// assert_eq!(tree[root], 42);
+    ///
+    /// let paths = tree.fold_down(root, String::new(), |path, _, data| format!("{}/{}", path, data));
+    /// let c = tree.descendants(root).find(|&node| tree[node] == "c").unwrap();
+    /// assert_eq!(paths[&c], "/a/b/c");
     /// ```
-    pub fn get_root_index(&self) -> Option<Index> {
-        self.root_index
+    pub fn fold_down<A, S>(&self, node_id: Index, init: A, mut step_fn: S) -> HashMap<Index, A>
+    where
+        S: FnMut(&A, Index, &T) -> A,
+    {
+        let mut values = HashMap::new();
+        let root_value = step_fn(&init, node_id, &self[node_id]);
+        values.insert(node_id, root_value);
+        for current_id in self.descendants(node_id).skip(1) {
+            let parent_id = self.nodes[current_id].parent.unwrap();
+            let value = step_fn(&values[&parent_id], current_id, &self[current_id]);
+            values.insert(current_id, value);
+        }
+        values
     }
 
-    /// Get the capacity of this tree.
+    /// Find the first node, in tree order, among the node at index `node_id` and
+    /// its descendants for which `predicate` returns `true`.
     ///
-    /// The capacity is the maximum number of elements the tree can hold
-    /// without further allocation, including however many it currently
-    /// contains.
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
     ///
     /// # Examples
     ///
     /// ```
     /// use vec_tree::VecTree;
     ///
-    /// let mut tree = VecTree::with_capacity(10);
-    /// let root = tree.insert_root(0);
+    /// let tree = VecTree::from_sexpr("(1 (2 3 4) 5)", |atom| atom.parse::<i32>().unwrap()).unwrap();
+    /// let root = tree.get_root_index().unwrap();
     ///
-    /// // `try_insert` does not allocate new capacity.
-    /// for i in 1..10 {
-    ///     assert!(tree.try_insert(i, root).is_ok());
-    ///     assert_eq!(tree.capacity(), 10);
-    /// }
+    /// let odd = tree.find(root, |_, &data| data > 1 && data % 2 == 1).unwrap();
+    /// assert_eq!(tree[odd], 3);
+    /// assert_eq!(tree.find(root, |_, &data| data > 5), None);
+    /// ```
+    pub fn find<P>(&self, node_id: Index, mut predicate: P) -> Option<Index>
+    where
+        P: FnMut(Index, &T) -> bool,
+    {
+        self.descendants(node_id).find(|&id| predicate(id, &self[id]))
+    }
+
+    /// Return an iterator of the nodes, in tree order, among the node at index
+    /// `node_id` and its descendants for which `predicate` returns `true`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
+    ///
+    /// # Examples
     ///
-    /// // But `insert` will if the root is already at capacity.
-    /// tree.insert(11, root);
-    /// assert!(tree.capacity() > 10);
     /// ```
-    pub fn capacity(&self) -> usize {
-        self.nodes.capacity()
+    /// use vec_tree::VecTree;
+    ///
+    /// let tree = VecTree::from_sexpr("(1 (2 3 4) 5)", |atom| atom.parse::<i32>().unwrap()).unwrap();
+    /// let root = tree.get_root_index().unwrap();
+    ///
+    /// let odd = tree.find_all(root, |_, &data| data % 2 == 1).collect::<Vec<_>>();
+    /// assert_eq!(odd.iter().map(|&node| tree[node]).collect::<Vec<_>>(), [1, 3, 5]);
+    /// ```
+    pub fn find_all<P>(&self, node_id: Index, predicate: P) -> FindAllIter<'_, T, P>
+    where
+        P: FnMut(Index, &T) -> bool,
+    {
+        FindAllIter {
+            descendants: self.descendants(node_id),
+            predicate,
+        }
     }
 
-    /// Clear all the items inside the tree, but keep its allocation.
+    /// Find the position, among the children of the node at index `node_id`, of
+    /// the first child for which `predicate` returns `true`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
     ///
     /// # Examples
     ///
     /// ```
     /// use vec_tree::VecTree;
     ///
-    /// let mut tree = VecTree::with_capacity(1);
-    /// let root = tree.insert_root(42);
-    /// tree.insert(43, root); // The capacity is doubled when reached.
+    /// let tree = VecTree::from_sexpr("(1 (2 3 4) 5)", |atom| atom.parse::<i32>().unwrap()).unwrap();
+    /// let root = tree.get_root_index().unwrap();
     ///
-    /// tree.clear();
-    /// assert_eq!(tree.capacity(), 2);
+    /// assert_eq!(tree.position_child(root, |_, &data| data == 5), Some(1));
+    /// assert_eq!(tree.position_child(root, |_, &data| data == 3), None);
     /// ```
-    pub fn clear(&mut self) {
-        self.nodes.clear();
-        self.root_index = None;
+    pub fn position_child<P>(&self, node_id: Index, mut predicate: P) -> Option<usize>
+    where
+        P: FnMut(Index, &T) -> bool,
+    {
+        self.children(node_id).position(|id| predicate(id, &self[id]))
+    }
+
+    /// Walk down from the node at index `node_id`, going at each step to the
+    /// first child whose key, as given by `key_fn`, matches the next key of
+    /// `path`.
+    ///
+    /// Returns the node reached at the end of the path, or `None` if a key
+    /// couldn't be matched. An empty path leads to the node itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let tree = VecTree::from_sexpr("(/ (usr (lib libc.so)) (home (user notes.txt)))", |atom| {
+    ///     atom.to_string()
+    /// })
+    /// .unwrap();
+    /// let root = tree.get_root_index().unwrap();
+    ///
+    /// let notes = tree.resolve_path(root, &["home", "user", "notes.txt"], String::as_str).unwrap();
+    /// assert_eq!(tree[notes], "notes.txt");
+    /// assert_eq!(tree.resolve_path(root, &["usr", "bin"], String::as_str), None);
+    /// assert_eq!(tree.resolve_path(root, &[], String::as_str), Some(root));
+    /// ```
+    pub fn resolve_path<K, F>(&self, node_id: Index, path: &[&K], key_fn: F) -> Option<Index>
+    where
+        K: ?Sized + PartialEq,
+        F: Fn(&T) -> &K,
+    {
+        let mut current_id = node_id;
+        for &key in path {
+            current_id = self
+                .children(current_id)
+                .find(|&child_id| key_fn(&self[child_id]) == key)?;
+        }
+        Some(current_id)
     }
 
     /// Return an iterator of references to this node’s parent.
@@ -921,7 +2511,7 @@ impl<T> VecTree<T> {
     #[inline]
     pub fn has_children(&self, node_id: Index) -> bool {
         match self.nodes.get(node_id) {
-            Some(node) => !(node.first_child == None && node.last_child == None),
+            Some(node) => !(node.first_child.is_none() && node.last_child.is_none()),
             None => false
         }
     }
@@ -931,7 +2521,7 @@ impl<T> VecTree<T> {
     ///
     /// This version for use in filter predicates
     #[inline]
-    pub fn is_leaf_ref(&self, node_id: &Index) -> bool { !self.has_children(node_id.clone()) }
+    pub fn is_leaf_ref(&self, node_id: &Index) -> bool { !self.has_children(*node_id) }
 
     /// Return whether or not the node is a leaf
     /// (i.e. doesn't have children)
@@ -941,17 +2531,139 @@ impl<T> VecTree<T> {
     pub fn is_leaf(&self, node_id: Index) -> bool { !self.has_children(node_id) }
 
     /// Return an iterator of references to this node’s children.
-    pub fn children(&self, node_id: Index) -> ChildrenIter<T> {
+    pub fn children(&self, node_id: Index) -> ChildrenIter<'_, T> {
         ChildrenIter {
             tree: self,
             node_id: self.nodes[node_id].first_child,
         }
     }
 
+    /// Index the children of the node at index `node_id` by position, so that
+    /// [`nth_child`](#method.nth_child), [`child_index_of`](#method.child_index_of)
+    /// and [`children_range`](#method.children_range) take logarithmic time
+    /// instead of linear time for them.
+    ///
+    /// The index is kept up to date as children are inserted, moved and removed,
+    /// each of these taking logarithmic time as well, and is dropped along with
+    /// the node. It is worth it for nodes with many children.
+    ///
+    /// Returns `false` if the node is not in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// tree.index_children(root);
+    ///
+    /// let children = (1..=1000).map(|i| tree.insert(i, root)).collect::<Vec<_>>();
+    /// assert_eq!(tree.nth_child(root, 499), Some(children[499]));
+    ///
+    /// tree.remove(children[0]);
+    /// assert_eq!(tree.child_index_of(children[500]), Some(499));
+    /// assert_eq!(tree.children_range(root, 0..3).map(|child| tree[child]).collect::<Vec<_>>(), [2, 3, 4]);
+    /// ```
+    pub fn index_children(&mut self, node_id: Index) -> bool {
+        if !self.contains(node_id) {
+            return false;
+        }
+        if !self.child_orders.contains_key(&node_id) {
+            let order = ChildOrder::new(self.children(node_id));
+            self.child_orders.insert(node_id, order);
+        }
+        true
+    }
+
+    /// Drop the index of the children of the node at index `node_id` built by
+    /// [`index_children`](#method.index_children), returning whether there was one.
+    pub fn unindex_children(&mut self, node_id: Index) -> bool {
+        self.child_orders.remove(&node_id).is_some()
+    }
+
+    /// Return the `n`th child of the node at index `node_id`, counting from 0, or
+    /// `None` if it has no such child or is not in the tree.
+    ///
+    /// This takes logarithmic time if the children of the node are indexed with
+    /// [`index_children`](#method.index_children), and linear time otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let first = tree.insert(1, root);
+    /// let second = tree.insert(2, root);
+    ///
+    /// assert_eq!(tree.nth_child(root, 1), Some(second));
+    /// assert_eq!(tree.nth_child(root, 2), None);
+    /// assert_eq!(tree.nth_child(first, 0), None);
+    /// ```
+    pub fn nth_child(&self, node_id: Index, n: usize) -> Option<Index> {
+        match self.child_orders.get(&node_id) {
+            Some(order) => order.nth(n),
+            None if self.contains(node_id) => self.children(node_id).nth(n),
+            None => None,
+        }
+    }
+
+    /// Return the position of the node at index `node_id` among its siblings,
+    /// counting from 0, or `None` if it is the root node or is not in the tree.
+    ///
+    /// This takes logarithmic time if the children of its parent are indexed with
+    /// [`index_children`](#method.index_children), and linear time otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let first = tree.insert(1, root);
+    /// let second = tree.insert(2, root);
+    ///
+    /// assert_eq!(tree.child_index_of(second), Some(1));
+    /// tree.append_child(root, first);
+    /// assert_eq!(tree.child_index_of(second), Some(0));
+    /// assert_eq!(tree.child_index_of(root), None);
+    /// ```
+    pub fn child_index_of(&self, node_id: Index) -> Option<usize> {
+        let parent_id = self.nodes.get(node_id)?.parent?;
+        match self.child_orders.get(&parent_id) {
+            Some(order) => order.position(node_id),
+            None => Some(self.preceding_siblings(node_id).count() - 1),
+        }
+    }
+
+    /// Return an iterator of references to the children of the node at index
+    /// `node_id` whose positions, counting from 0, are in `range`.
+    ///
+    /// Finding the first of them takes logarithmic time if the children of the
+    /// node are indexed with [`index_children`](#method.index_children), and
+    /// linear time otherwise.
+    ///
+    /// If the node is not in the tree, the iterator is empty.
+    pub fn children_range(&self, node_id: Index, range: ops::Range<usize>) -> iter::Take<ChildrenIter<'_, T>> {
+        let first_child = if range.start >= range.end {
+            None
+        } else {
+            self.nth_child(node_id, range.start)
+        };
+        ChildrenIter {
+            tree: self,
+            node_id: first_child,
+        }
+        .take(range.end.saturating_sub(range.start))
+    }
+
     /// Return an iterator of references to this node and the siblings before it.
     ///
     /// Call `.next().unwrap()` once on the iterator to skip the node itself.
-    pub fn preceding_siblings(&self, node_id: Index) -> PrecedingSiblingsIter<T> {
+    pub fn preceding_siblings(&self, node_id: Index) -> PrecedingSiblingsIter<'_, T> {
         PrecedingSiblingsIter {
             tree: self,
             node_id: Some(node_id),
@@ -961,7 +2673,7 @@ impl<T> VecTree<T> {
     /// Return an iterator of references to this node and the siblings after it.
     ///
     /// Call `.next().unwrap()` once on the iterator to skip the node itself.
-    pub fn following_siblings(&self, node_id: Index) -> FollowingSiblingsIter<T> {
+    pub fn following_siblings(&self, node_id: Index) -> FollowingSiblingsIter<'_, T> {
         FollowingSiblingsIter {
             tree: self,
             node_id: Some(node_id),
@@ -971,7 +2683,7 @@ impl<T> VecTree<T> {
     /// Return an iterator of references to this node and its ancestors.
     ///
     /// Call `.next().unwrap()` once on the iterator to skip the node itself.
-    pub fn ancestors(&self, node_id: Index) -> AncestorsIter<T> {
+    pub fn ancestors(&self, node_id: Index) -> AncestorsIter<'_, T> {
         AncestorsIter {
             tree: self,
             node_id: Some(node_id),
@@ -979,7 +2691,7 @@ impl<T> VecTree<T> {
     }
 
     /// Return an iterator of references to this node and its descendants, in tree order.
-    fn traverse(&self, node_id: Index) -> TraverseIter<T> {
+    fn traverse(&self, node_id: Index) -> TraverseIter<'_, T> {
         TraverseIter {
             tree: self,
             root: node_id,
@@ -989,7 +2701,7 @@ impl<T> VecTree<T> {
 
     /// Return an iterator of references to this node and its descendants, with deoth in the tree,
     /// in tree order.
-    fn traverse_with_depth(&self, node_id: Index) -> TraverseWithDepthIter<T> {
+    fn traverse_with_depth(&self, node_id: Index) -> TraverseWithDepthIter<'_, T> {
         TraverseWithDepthIter {
             tree: self,
             root: node_id,
@@ -1001,7 +2713,7 @@ impl<T> VecTree<T> {
     ///
     /// Parent nodes appear before the descendants.
     /// Call `.next().unwrap()` once on the iterator to skip the node itself.
-    pub fn descendants(&self, node_id: Index) -> DescendantsIter<T> {
+    pub fn descendants(&self, node_id: Index) -> DescendantsIter<'_, T> {
         DescendantsIter(self.traverse(node_id))
     }
 
@@ -1010,7 +2722,7 @@ impl<T> VecTree<T> {
     ///
     /// Parent nodes appear before the descendants.
     /// Call `.next().unwrap()` once on the iterator to skip the node itself.
-    pub fn descendants_with_depth(&self, node_id: Index) -> DescendantsWithDepthIter<T> {
+    pub fn descendants_with_depth(&self, node_id: Index) -> DescendantsWithDepthIter<'_, T> {
         DescendantsWithDepthIter(self.traverse_with_depth(node_id))
     }
 }
@@ -1039,6 +2751,134 @@ impl<T> ops::IndexMut<Index> for VecTree<T> {
     }
 }
 
+impl<T> VecTree<T> {
+    /// Compare the subtree rooted at `node_id` with the subtree of `other` rooted at
+    /// `other_node_id`, node by node in tree order, using `compare` for the data.
+    ///
+    /// A node that has run out of children is ordered before a node that still has some.
+    fn compare_subtrees<F>(
+        &self,
+        node_id: Index,
+        other: &VecTree<T>,
+        other_node_id: Index,
+        mut compare: F,
+    ) -> Option<Ordering>
+    where
+        F: FnMut(&T, &T) -> Option<Ordering>,
+    {
+        let mut edges = self.traverse(node_id);
+        let mut other_edges = other.traverse(other_node_id);
+        loop {
+            match (edges.next(), other_edges.next()) {
+                (None, None) => return Some(Ordering::Equal),
+                (Some(NodeEdge::Start(node_id)), Some(NodeEdge::Start(other_node_id))) => {
+                    match compare(&self[node_id], &other[other_node_id]) {
+                        Some(Ordering::Equal) => {}
+                        ordering => return ordering,
+                    }
+                }
+                (Some(NodeEdge::End(_)), Some(NodeEdge::End(_))) => {}
+                (Some(NodeEdge::End(_)), _) | (None, _) => return Some(Ordering::Less),
+                (Some(NodeEdge::Start(_)), _) => return Some(Ordering::Greater),
+            }
+        }
+    }
+}
+
+impl<T: PartialEq> VecTree<T> {
+    /// Return whether the subtree rooted at `node_id` has the same shape and data
+    /// as the subtree of `other` rooted at `other_node_id`.
+    ///
+    /// The order of the children matters, but where the nodes are stored doesn't:
+    /// their indices and the free slots of the trees are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the nodes is not in its tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(0);
+    /// let left = tree.insert(1, root);
+    /// tree.insert(2, left);
+    /// let right = tree.insert(1, root);
+    ///
+    /// assert!(!tree.subtree_eq(left, &tree, right));
+    /// tree.insert(2, right);
+    /// assert!(tree.subtree_eq(left, &tree, right));
+    /// ```
+    pub fn subtree_eq(&self, node_id: Index, other: &VecTree<T>, other_node_id: Index) -> bool {
+        let ordering = self.compare_subtrees(node_id, other, other_node_id, |a, b| {
+            if a == b {
+                Some(Ordering::Equal)
+            } else {
+                None
+            }
+        });
+        ordering == Some(Ordering::Equal)
+    }
+}
+
+/// Two trees are equal when they have the same shape and data, wherever their
+/// nodes are stored.
+impl<T: PartialEq> PartialEq for VecTree<T> {
+    fn eq(&self, other: &VecTree<T>) -> bool {
+        match (self.root_index, other.root_index) {
+            (Some(root_id), Some(other_root_id)) => self.subtree_eq(root_id, other, other_root_id),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T: Eq> Eq for VecTree<T> {}
+
+impl<T: Hash> Hash for VecTree<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.root_index.is_some().hash(state);
+        if let Some(root_id) = self.root_index {
+            for edge in self.traverse(root_id) {
+                match edge {
+                    NodeEdge::Start(node_id) => {
+                        true.hash(state);
+                        self[node_id].hash(state);
+                    }
+                    NodeEdge::End(_) => false.hash(state),
+                }
+            }
+        }
+    }
+}
+
+/// Trees are ordered by comparing their nodes in tree order. When a node has
+/// fewer children than the other, it is the lesser one. An empty tree is
+/// lesser than any other.
+impl<T: PartialOrd> PartialOrd for VecTree<T> {
+    fn partial_cmp(&self, other: &VecTree<T>) -> Option<Ordering> {
+        match (self.root_index, other.root_index) {
+            (Some(root_id), Some(other_root_id)) => {
+                self.compare_subtrees(root_id, other, other_root_id, T::partial_cmp)
+            }
+            (root_index, other_root_index) => root_index.is_some().partial_cmp(&other_root_index.is_some()),
+        }
+    }
+}
+
+impl<T: Ord> Ord for VecTree<T> {
+    fn cmp(&self, other: &VecTree<T>) -> Ordering {
+        match (self.root_index, other.root_index) {
+            (Some(root_id), Some(other_root_id)) => self
+                .compare_subtrees(root_id, other, other_root_id, |a, b| Some(a.cmp(b)))
+                .unwrap(),
+            (root_index, other_root_index) => root_index.is_some().cmp(&other_root_index.is_some()),
+        }
+    }
+}
+
 macro_rules! impl_node_iterator {
     ($name:ident, $next:expr) => {
         impl<'a, T> Iterator for $name<'a, T> {
@@ -1125,15 +2965,11 @@ impl<'a, T> Iterator for TraverseIter<'a, T> {
                             match self.tree.nodes[node_id].next_sibling {
                                 Some(next_sibling) => Some(NodeEdge::Start(next_sibling)),
                                 None => {
-                                    match self.tree.nodes[node_id].parent {
-                                        Some(parent) => Some(NodeEdge::End(parent)),
-
-                                        // `self.tree.nodes[node_id].parent` here can only be `None`
-                                        // if the tree has been modified during iteration, but
-                                        // silently stoping iteration seems a more sensible behavior
-                                        // than panicking.
-                                        None => None,
-                                    }
+                                    // `self.tree.nodes[node_id].parent` here can only be `None`
+                                    // if the tree has been modified during iteration, but
+                                    // silently stoping iteration seems a more sensible behavior
+                                    // than panicking.
+                                    self.tree.nodes[node_id].parent.map(NodeEdge::End)
                                 }
                             }
                         }
@@ -1146,6 +2982,94 @@ impl<'a, T> Iterator for TraverseIter<'a, T> {
     }
 }
 
+/// An iterator of the nodes matching a predicate among a given node and its descendants, in
+/// tree order.
+pub struct FindAllIter<'a, T: 'a, P> {
+    descendants: DescendantsIter<'a, T>,
+    predicate: P,
+}
+
+impl<'a, T, P> Iterator for FindAllIter<'a, T, P>
+where
+    P: FnMut(Index, &T) -> bool,
+{
+    type Item = Index;
+
+    fn next(&mut self) -> Option<Index> {
+        let tree = self.descendants.0.tree;
+        let predicate = &mut self.predicate;
+        self.descendants.find(|&node_id| predicate(node_id, &tree[node_id]))
+    }
+}
+
+/// The order in which [`VecTree::drain_subtree`](./struct.VecTree.html#method.drain_subtree)
+/// yields the nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrainOrder {
+    /// Parent nodes before their descendants.
+    PreOrder,
+    /// Parent nodes after their descendants.
+    PostOrder,
+}
+
+/// An iterator removing a given node and its descendants from the tree, yielding their data
+/// along with their depth.
+///
+/// The nodes not yielded yet are removed when the iterator is dropped.
+pub struct DrainSubtreeIter<'a, T: 'a> {
+    tree: &'a mut VecTree<T>,
+    root: Index,
+    order: DrainOrder,
+    // In pre-order, the nodes to yield next, the last one first. In post-order, the node below
+    // which the next node is the leftmost leaf.
+    pending: Vec<(Index, u32)>,
+}
+
+impl<'a, T> Iterator for DrainSubtreeIter<'a, T> {
+    type Item = (u32, T);
+
+    fn next(&mut self) -> Option<(u32, T)> {
+        let (node_id, depth) = self.pending.pop()?;
+        match self.order {
+            DrainOrder::PreOrder => {
+                let node = self.tree.remove_node(node_id).unwrap();
+                let mut child_id = node.last_child;
+                while let Some(id) = child_id {
+                    self.pending.push((id, depth + 1));
+                    child_id = self.tree.nodes[id].previous_sibling;
+                }
+                Some((depth, node.data))
+            }
+            DrainOrder::PostOrder => {
+                let (mut leaf_id, mut leaf_depth) = (node_id, depth);
+                while let Some(child_id) = self.tree.nodes[leaf_id].first_child {
+                    leaf_id = child_id;
+                    leaf_depth += 1;
+                }
+
+                let leaf = self.tree.remove_node(leaf_id).unwrap();
+                if leaf_id != self.root {
+                    // The leaf is the first child of its parent, which becomes a leaf once
+                    // its last child is removed.
+                    let parent_id = leaf.parent.unwrap();
+                    self.tree.nodes[parent_id].first_child = leaf.next_sibling;
+                    match leaf.next_sibling {
+                        Some(next_sibling_id) => self.pending.push((next_sibling_id, leaf_depth)),
+                        None => self.pending.push((parent_id, leaf_depth - 1)),
+                    }
+                }
+                Some((leaf_depth, leaf.data))
+            }
+        }
+    }
+}
+
+impl<'a, T> Drop for DrainSubtreeIter<'a, T> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
 /// An iterator of references to a given node and its descendants, in tree order.
 pub struct DescendantsIter<'a, T: 'a>(pub TraverseIter<'a, T>);
 
@@ -1208,17 +3132,13 @@ impl<'a, T> Iterator for TraverseWithDepthIter<'a, T> {
                                     Some(NodeEdgeWithDepth::Start(next_sibling, depth))
                                 }
                                 None => {
-                                    match self.tree.nodes[node_id].parent {
-                                        Some(parent) => {
-                                            Some(NodeEdgeWithDepth::End(parent, depth - 1))
-                                        }
-
-                                        // `self.tree.nodes[node_id].parent` here can only be `None`
-                                        // if the tree has been modified during iteration, but
-                                        // silently stoping iteration seems a more sensible behavior
-                                        // than panicking.
-                                        None => None,
-                                    }
+                                    // `self.tree.nodes[node_id].parent` here can only be `None`
+                                    // if the tree has been modified during iteration, but
+                                    // silently stoping iteration seems a more sensible behavior
+                                    // than panicking.
+                                    self.tree.nodes[node_id]
+                                        .parent
+                                        .map(|parent| NodeEdgeWithDepth::End(parent, depth - 1))
                                 }
                             }
                         }
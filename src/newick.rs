@@ -0,0 +1,376 @@
+//! Reading and writing a `VecTree` in the
+//! [Newick format](https://en.wikipedia.org/wiki/Newick_format), as used for
+//! phylogenetic trees: `((A:0.1,B:0.2)E:0.5,C:0.3)F;`.
+//!
+//! Every node may have a label and a branch length. Unquoted labels can't
+//! contain whitespace or any of `()[]':;,`, and their underscores are read as
+//! spaces. Quoted labels are written between single quotes, a quote inside
+//! them being doubled. Comments between square brackets are ignored wherever
+//! whitespace is allowed.
+//!
+//! The data of the nodes is built and read through the `NewickData` trait,
+//! which is implemented by `NewickNode` for the simple cases.
+
+use super::{NodeEdge, VecTree};
+use generational_arena::Index;
+use std::{error, fmt};
+
+/// The data of a node that can be read from and written to Newick.
+pub trait NewickData {
+    /// Build the data of a node from its label and branch length.
+    fn from_newick(label: Option<String>, branch_length: Option<f64>) -> Self;
+
+    /// The label of the node, if it has one.
+    fn newick_label(&self) -> Option<&str>;
+
+    /// The length of the branch leading to the node, if it has one.
+    fn newick_branch_length(&self) -> Option<f64>;
+}
+
+/// A node of a Newick tree, holding just a label and a branch length.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NewickNode {
+    pub label: Option<String>,
+    pub branch_length: Option<f64>,
+}
+
+impl NewickData for NewickNode {
+    fn from_newick(label: Option<String>, branch_length: Option<f64>) -> Self {
+        NewickNode { label, branch_length }
+    }
+
+    fn newick_label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    fn newick_branch_length(&self) -> Option<f64> {
+        self.branch_length
+    }
+}
+
+/// The reason a Newick string could not be read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The input ended before the closing `;`.
+    UnexpectedEof,
+    /// A character that is not allowed at this place.
+    UnexpectedCharacter(char),
+    /// A quoted label is missing its closing `'`.
+    UnterminatedQuote,
+    /// A comment is missing its closing `]`.
+    UnterminatedComment,
+    /// A branch length that is not a number.
+    InvalidBranchLength,
+    /// There is something left after the closing `;`.
+    TrailingInput,
+}
+
+/// An error returned when reading a malformed Newick string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset in the input where the error was found.
+    pub position: usize,
+    /// What went wrong.
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::UnexpectedEof => write!(f, "unexpected end of input")?,
+            ParseErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character {:?}", c)?,
+            ParseErrorKind::UnterminatedQuote => write!(f, "unterminated quoted label")?,
+            ParseErrorKind::UnterminatedComment => write!(f, "unterminated comment")?,
+            ParseErrorKind::InvalidBranchLength => write!(f, "invalid branch length")?,
+            ParseErrorKind::TrailingInput => write!(f, "unexpected input after `;`")?,
+        }
+        write!(f, " at position {}", self.position)
+    }
+}
+
+impl error::Error for ParseError {}
+
+const RESERVED: &[char] = &['(', ')', '[', ']', '\'', ':', ';', ','];
+
+struct Cursor<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            position: self.position,
+            kind,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    /// Skip whitespace and comments, then return the next character without consuming it.
+    fn peek(&mut self) -> Result<Option<char>, ParseError> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+            if !trimmed.starts_with('[') {
+                return Ok(trimmed.chars().next());
+            }
+            match trimmed.find(']') {
+                Some(end) => self.position += end + 1,
+                None => return Err(self.error(ParseErrorKind::UnterminatedComment)),
+            }
+        }
+    }
+
+    fn expect_any(&mut self) -> Result<char, ParseError> {
+        match self.peek()? {
+            Some(c) => {
+                self.position += c.len_utf8();
+                Ok(c)
+            }
+            None => Err(self.error(ParseErrorKind::UnexpectedEof)),
+        }
+    }
+
+    fn label(&mut self) -> Result<Option<String>, ParseError> {
+        if self.peek()? == Some('\'') {
+            let start = self.position;
+            let mut label = String::new();
+            let mut chars = self.rest().char_indices().skip(1).peekable();
+            loop {
+                match chars.next() {
+                    None => {
+                        self.position = start;
+                        return Err(self.error(ParseErrorKind::UnterminatedQuote));
+                    }
+                    Some((_, '\'')) if chars.peek().map(|&(_, c)| c) == Some('\'') => {
+                        chars.next();
+                        label.push('\'');
+                    }
+                    Some((offset, '\'')) => {
+                        self.position += offset + 1;
+                        return Ok(Some(label));
+                    }
+                    Some((_, c)) => label.push(c),
+                }
+            }
+        }
+
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || RESERVED.contains(&c))
+            .unwrap_or(rest.len());
+        self.position += end;
+        if end == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(rest[..end].replace('_', " ")))
+        }
+    }
+
+    fn branch_length(&mut self) -> Result<Option<f64>, ParseError> {
+        if self.peek()? != Some(':') {
+            return Ok(None);
+        }
+        self.position += 1;
+        self.peek()?;
+        // Taking the whole word lets `inf` and `NaN` be read back as they are written.
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || RESERVED.contains(&c))
+            .unwrap_or(rest.len());
+        match rest[..end].parse() {
+            Ok(length) => {
+                self.position += end;
+                Ok(Some(length))
+            }
+            Err(_) => Err(self.error(ParseErrorKind::InvalidBranchLength)),
+        }
+    }
+}
+
+fn write_label(out: &mut String, label: &str) {
+    if !label.is_empty() && !label.contains(|c: char| c.is_whitespace() || c == '_' || RESERVED.contains(&c)) {
+        out.push_str(label);
+        return;
+    }
+    out.push('\'');
+    out.push_str(&label.replace('\'', "''"));
+    out.push('\'');
+}
+
+impl<T: NewickData> VecTree<T> {
+    /// Read a tree from a Newick string, ending with a `;`.
+    ///
+    /// See the [`newick` module](./newick/index.html) for the format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    /// use vec_tree::newick::NewickNode;
+    ///
+    /// let tree = VecTree::<NewickNode>::from_newick("((A:0.1,B:0.2)E:0.5,'C d':0.3)F;").unwrap();
+    ///
+    /// let root = tree.get_root_index().unwrap();
+    /// assert_eq!(tree[root].label.as_deref(), Some("F"));
+    /// let labels = tree
+    ///     .descendants(root)
+    ///     .map(|node| tree[node].label.clone().unwrap())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(labels, ["F", "E", "A", "B", "C d"]);
+    ///
+    /// let error = VecTree::<NewickNode>::from_newick("(A:0.1,B:x);").unwrap_err();
+    /// assert_eq!(error.position, 9);
+    /// ```
+    pub fn from_newick(input: &str) -> Result<VecTree<T>, ParseError> {
+        let mut cursor = Cursor { input, position: 0 };
+        // The label and branch length of an internal node only come after its
+        // children, so the nodes are read in post-order, along with their number
+        // of children, and the tree is built once they are all read.
+        let mut post_order: Vec<(T, usize)> = Vec::new();
+        // The number of children read so far of each internal node whose closing
+        // `)` hasn't been read yet.
+        let mut open_nodes: Vec<usize> = Vec::new();
+        // Whether the next thing to read is a subtree, rather than what follows one.
+        let mut expecting_subtree = true;
+
+        loop {
+            if expecting_subtree {
+                if cursor.peek()? == Some('(') {
+                    cursor.position += 1;
+                    open_nodes.push(0);
+                    continue;
+                }
+                let label = cursor.label()?;
+                post_order.push((T::from_newick(label, cursor.branch_length()?), 0));
+                if let Some(child_count) = open_nodes.last_mut() {
+                    *child_count += 1;
+                }
+                expecting_subtree = false;
+                continue;
+            }
+
+            let position = cursor.position;
+            match cursor.expect_any()? {
+                ',' if !open_nodes.is_empty() => expecting_subtree = true,
+                ')' if !open_nodes.is_empty() => {
+                    let child_count = open_nodes.pop().unwrap();
+                    let label = cursor.label()?;
+                    post_order.push((T::from_newick(label, cursor.branch_length()?), child_count));
+                    if let Some(child_count) = open_nodes.last_mut() {
+                        *child_count += 1;
+                    }
+                }
+                ';' if open_nodes.is_empty() => break,
+                c => {
+                    cursor.position = position;
+                    return Err(cursor.error(ParseErrorKind::UnexpectedCharacter(c)));
+                }
+            }
+        }
+
+        if cursor.peek()?.is_some() {
+            return Err(cursor.error(ParseErrorKind::TrailingInput));
+        }
+
+        // In reverse post-order, every node comes after its parent, and after its
+        // next sibling, so it is prepended to the children of its parent.
+        let mut tree = VecTree::with_capacity(post_order.len());
+        // The nodes some of whose children haven't been built yet, with their number.
+        let mut parents: Vec<(Index, usize)> = Vec::new();
+        while let Some((data, child_count)) = post_order.pop() {
+            while parents.last().is_some_and(|&(_, remaining)| remaining == 0) {
+                parents.pop();
+            }
+            let node_id = tree.create_node(data);
+            match parents.last_mut() {
+                Some((parent_id, remaining)) => {
+                    *remaining -= 1;
+                    let parent_id = *parent_id;
+                    tree.link_first_child(parent_id, node_id);
+                }
+                None => tree.root_index = Some(node_id),
+            }
+            if child_count > 0 {
+                parents.push((node_id, child_count));
+            }
+        }
+        Ok(tree)
+    }
+
+    /// Write the whole tree in the Newick format, ending with a `;`.
+    ///
+    /// An empty tree is written as an empty string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    /// use vec_tree::newick::NewickNode;
+    ///
+    /// let node = |label: &str, branch_length| NewickNode {
+    ///     label: Some(label.to_string()),
+    ///     branch_length,
+    /// };
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root(node("F", None));
+    /// let e = tree.insert(node("E", Some(0.5)), root);
+    /// tree.insert(node("A", Some(0.1)), e);
+    /// tree.insert(node("B", Some(0.2)), e);
+    /// tree.insert(node("C d", Some(0.3)), root);
+    ///
+    /// assert_eq!(tree.to_newick(), "((A:0.1,B:0.2)E:0.5,'C d':0.3)F;");
+    /// ```
+    pub fn to_newick(&self) -> String {
+        match self.root_index {
+            Some(root_id) => self.subtree_to_newick(root_id),
+            None => String::new(),
+        }
+    }
+
+    /// Write the subtree rooted at `node_id` in the Newick format, ending with a `;`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
+    pub fn subtree_to_newick(&self, node_id: Index) -> String {
+        let mut out = String::new();
+        let write_node = |out: &mut String, data: &T| {
+            if let Some(label) = data.newick_label() {
+                write_label(out, label);
+            }
+            if let Some(branch_length) = data.newick_branch_length() {
+                out.push(':');
+                out.push_str(&branch_length.to_string());
+            }
+        };
+
+        for edge in self.traverse(node_id) {
+            match edge {
+                NodeEdge::Start(current_id) => {
+                    if current_id != node_id && self.nodes[current_id].previous_sibling.is_some() {
+                        out.push(',');
+                    }
+                    if self.has_children(current_id) {
+                        out.push('(');
+                    } else {
+                        write_node(&mut out, &self[current_id]);
+                    }
+                }
+                NodeEdge::End(current_id) => {
+                    if self.has_children(current_id) {
+                        out.push(')');
+                        write_node(&mut out, &self[current_id]);
+                    }
+                }
+            }
+        }
+        out.push(';');
+        out
+    }
+}
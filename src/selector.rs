@@ -0,0 +1,346 @@
+//! Selecting nodes with a small path language, in the spirit of XPath.
+//!
+//! A selector is a list of steps separated by `/`, each step going from the
+//! nodes selected so far to other nodes along an axis, keeping those whose
+//! name matches:
+//!
+//! - `child::a`, or just `a`, selects the children named `a`;
+//! - `descendant::a`, `parent::a`, `ancestor::a`, `following-sibling::a` and
+//!   `preceding-sibling::a` select the nodes named `a` along the other axes;
+//! - `self::a` keeps the node itself if it is named `a`;
+//! - `*` matches any name, as in `child::*` or `*`;
+//! - `.` stands for `self::*` and `..` for `parent::*`;
+//! - `//` between two steps, or at the start of the selector, makes the next
+//!   step start from the nodes selected so far and all their descendants, so
+//!   that `a//b` selects the nodes named `b` anywhere below `a`.
+//!
+//! A step can be followed by positional predicates, `a[2]` keeping the second
+//! node named `a` among those found from each node, counting from 1 in the
+//! order of the axis: nearest first for `parent`, `ancestor` and
+//! `preceding-sibling`, and tree order for the others. So `//p[1]` selects
+//! every node named `p` which is the first one among its siblings.
+//!
+//! The names of the nodes are given by a function from their data, and the
+//! nodes found are returned in tree order, without duplicates.
+
+use super::VecTree;
+use generational_arena::Index;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::{error, fmt};
+
+/// The reason a selector could not be read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The selector ended where a step was expected.
+    UnexpectedEnd,
+    /// A character that is not allowed at this place.
+    UnexpectedCharacter(char),
+    /// An axis which is not one of the supported ones.
+    UnknownAxis,
+    /// A positional predicate which is not a number from 1 on.
+    InvalidPosition,
+}
+
+/// An error returned when reading a malformed selector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset in the selector where the error was found.
+    pub position: usize,
+    /// What went wrong.
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::UnexpectedEnd => write!(f, "unexpected end of selector")?,
+            ParseErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character {:?}", c)?,
+            ParseErrorKind::UnknownAxis => write!(f, "unknown axis")?,
+            ParseErrorKind::InvalidPosition => write!(f, "invalid position")?,
+        }
+        write!(f, " at position {}", self.position)
+    }
+}
+
+impl error::Error for ParseError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Axis {
+    SelfNode,
+    Child,
+    Descendant,
+    Parent,
+    Ancestor,
+    FollowingSibling,
+    PrecedingSibling,
+}
+
+impl Axis {
+    fn from_name(name: &str) -> Option<Axis> {
+        match name {
+            "self" => Some(Axis::SelfNode),
+            "child" => Some(Axis::Child),
+            "descendant" => Some(Axis::Descendant),
+            "parent" => Some(Axis::Parent),
+            "ancestor" => Some(Axis::Ancestor),
+            "following-sibling" => Some(Axis::FollowingSibling),
+            "preceding-sibling" => Some(Axis::PrecedingSibling),
+            _ => None,
+        }
+    }
+
+    /// The nodes along this axis from `node_id`, in the order of the axis.
+    fn nodes<T>(self, tree: &VecTree<T>, node_id: Index) -> Vec<Index> {
+        match self {
+            Axis::SelfNode => vec![node_id],
+            Axis::Child => tree.children(node_id).collect(),
+            Axis::Descendant => tree.descendants(node_id).skip(1).collect(),
+            Axis::Parent => tree.parent(node_id).into_iter().collect(),
+            Axis::Ancestor => tree.ancestors(node_id).skip(1).collect(),
+            Axis::FollowingSibling => tree.following_siblings(node_id).skip(1).collect(),
+            Axis::PrecedingSibling => tree.preceding_siblings(node_id).skip(1).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Step {
+    // Set by a `//` before the step.
+    from_descendants: bool,
+    axis: Axis,
+    // `None` for `*`.
+    name: Option<String>,
+    // 1-based, applied in order.
+    positions: Vec<usize>,
+}
+
+/// A compiled selector, which can be run from any node of any tree.
+///
+/// See the [`selector` module](./index.html) for the syntax.
+///
+/// # Examples
+///
+/// ```
+/// use vec_tree::VecTree;
+/// use vec_tree::selector::Selector;
+///
+/// let tree = VecTree::from_sexpr("(html (body (p a b) (div (p c)) (p d)))", |atom| atom.to_string())
+///     .unwrap();
+/// let root = tree.get_root_index().unwrap();
+///
+/// let selector = Selector::parse("body//p[1]").unwrap();
+/// let first_paragraphs = selector.select(&tree, root, String::as_str);
+/// let contents = first_paragraphs
+///     .iter()
+///     .map(|&node| tree.children(node).map(|child| tree[child].as_str()).collect::<String>())
+///     .collect::<Vec<_>>();
+/// assert_eq!(contents, ["ab", "c"]);
+///
+/// let selector = Selector::parse("body/*/p").unwrap();
+/// assert_eq!(selector.select(&tree, root, String::as_str).len(), 1);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Selector {
+    steps: Vec<Step>,
+}
+
+/// The characters that can't be part of a name.
+fn ends_name(c: char) -> bool {
+    c.is_whitespace() || c == '/' || c == '[' || c == ']' || c == ':'
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, position: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError { position, kind }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.rest().chars().next() {
+            Some(c) => self.error(self.position, ParseErrorKind::UnexpectedCharacter(c)),
+            None => self.error(self.position, ParseErrorKind::UnexpectedEnd),
+        }
+    }
+
+    fn name(&mut self) -> &'a str {
+        let rest = self.rest();
+        let end = rest.find(ends_name).unwrap_or(rest.len());
+        self.position += end;
+        &rest[..end]
+    }
+
+    fn step(&mut self, from_descendants: bool) -> Result<Step, ParseError> {
+        let start = self.position;
+        let mut name = self.name();
+
+        if name == "." || name == ".." {
+            return Ok(Step {
+                from_descendants,
+                axis: if name == "." { Axis::SelfNode } else { Axis::Parent },
+                name: None,
+                positions: Vec::new(),
+            });
+        }
+
+        let mut axis = Axis::Child;
+        if self.rest().starts_with("::") {
+            axis = match Axis::from_name(name) {
+                Some(axis) => axis,
+                None => return Err(self.error(start, ParseErrorKind::UnknownAxis)),
+            };
+            self.position += 2;
+            name = self.name();
+        }
+        if name.is_empty() {
+            return Err(self.unexpected());
+        }
+
+        let mut positions = Vec::new();
+        while self.rest().starts_with('[') {
+            self.position += 1;
+            let rest = self.rest();
+            let end = match rest.find(']') {
+                Some(end) => end,
+                None => return Err(self.error(self.input.len(), ParseErrorKind::UnexpectedEnd)),
+            };
+            let digits = &rest[..end];
+            match digits.parse::<usize>() {
+                Ok(position) if position > 0 && digits.bytes().all(|b| b.is_ascii_digit()) => {
+                    positions.push(position)
+                }
+                _ => return Err(self.error(self.position, ParseErrorKind::InvalidPosition)),
+            }
+            self.position += end + 1;
+        }
+
+        Ok(Step {
+            from_descendants,
+            axis,
+            name: if name == "*" { None } else { Some(name.to_string()) },
+            positions,
+        })
+    }
+}
+
+impl Selector {
+    /// Compile a selector.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::selector::{ParseErrorKind, Selector};
+    ///
+    /// assert!(Selector::parse("a/following-sibling::*[1]").is_ok());
+    ///
+    /// let error = Selector::parse("a/sibling::b").unwrap_err();
+    /// assert_eq!(error.kind, ParseErrorKind::UnknownAxis);
+    /// assert_eq!(error.position, 2);
+    /// ```
+    pub fn parse(input: &str) -> Result<Selector, ParseError> {
+        let mut parser = Parser { input, position: 0 };
+        let mut steps = Vec::new();
+
+        let mut from_descendants = false;
+        if input.starts_with("//") {
+            parser.position += 2;
+            from_descendants = true;
+        }
+        loop {
+            steps.push(parser.step(from_descendants)?);
+            from_descendants = false;
+            let rest = parser.rest();
+            if rest.is_empty() {
+                break;
+            } else if rest.starts_with("//") {
+                parser.position += 2;
+                from_descendants = true;
+            } else if rest.starts_with('/') {
+                parser.position += 1;
+            } else {
+                return Err(parser.unexpected());
+            }
+        }
+
+        Ok(Selector { steps })
+    }
+
+    /// Run the selector from the node at index `node_id`, naming the nodes with
+    /// `name_fn`, and return the nodes found, in tree order.
+    ///
+    /// If `node_id` is not in the tree, nothing is found.
+    pub fn select<T, F>(&self, tree: &VecTree<T>, node_id: Index, name_fn: F) -> Vec<Index>
+    where
+        F: Fn(&T) -> &str,
+    {
+        if !tree.contains(node_id) {
+            return Vec::new();
+        }
+        // The nodes found are below the topmost node the steps can climb to, whose
+        // descendants are ranked in tree order to sort them.
+        let mut scope_id = node_id;
+        for step in &self.steps {
+            scope_id = match step.axis {
+                Axis::SelfNode | Axis::Child | Axis::Descendant => scope_id,
+                Axis::Parent | Axis::FollowingSibling | Axis::PrecedingSibling => {
+                    tree.parent(scope_id).unwrap_or(scope_id)
+                }
+                Axis::Ancestor => tree.ancestors(scope_id).last().unwrap(),
+            };
+        }
+        let ranks = tree
+            .descendants(scope_id)
+            .enumerate()
+            .map(|(rank, id)| (id, rank))
+            .collect::<HashMap<Index, usize>>();
+
+        let mut selected = vec![node_id];
+        for step in &self.steps {
+            if step.from_descendants {
+                let mut seen = HashSet::new();
+                selected = selected
+                    .iter()
+                    .flat_map(|&selected_id| tree.descendants(selected_id))
+                    .filter(|&descendant_id| seen.insert(descendant_id))
+                    .collect();
+            }
+
+            let mut found = Vec::new();
+            for &context_id in &selected {
+                let mut candidates = step
+                    .axis
+                    .nodes(tree, context_id)
+                    .into_iter()
+                    .filter(|&candidate_id| match step.name {
+                        Some(ref name) => name_fn(&tree[candidate_id]) == name,
+                        None => true,
+                    })
+                    .collect::<Vec<Index>>();
+                for &position in &step.positions {
+                    candidates = candidates.get(position - 1).cloned().into_iter().collect();
+                }
+                found.extend(candidates);
+            }
+            found.sort_unstable_by_key(|candidate_id| ranks[candidate_id]);
+            found.dedup();
+            selected = found;
+        }
+        selected
+    }
+}
+
+impl FromStr for Selector {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Selector, ParseError> {
+        Selector::parse(input)
+    }
+}
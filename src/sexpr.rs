@@ -0,0 +1,276 @@
+//! Reading and writing a `VecTree` as S-expressions.
+//!
+//! A node with children is written as a list whose head is the node itself,
+//! followed by its children: `(root (a x y) b)`. A leaf is written as a bare
+//! atom; a list holding a single atom, like `(b)`, is read as a leaf too.
+//!
+//! Atoms that are empty or contain whitespace, parentheses, double quotes or
+//! backslashes are written between double quotes, with `"` and `\` escaped by
+//! a backslash.
+
+use super::{NodeEdge, VecTree};
+use generational_arena::Index;
+use std::{error, fmt};
+
+/// The reason an S-expression could not be read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The input ended before the tree was complete.
+    UnexpectedEof,
+    /// A `)` was found that doesn't close any list.
+    UnexpectedCloseParen,
+    /// A list is empty: `()`.
+    EmptyList,
+    /// The head of a list is a list instead of an atom: `((a) b)`.
+    ExpectedAtom,
+    /// A quoted atom is missing its closing `"`.
+    UnterminatedString,
+    /// There is something left after the root expression.
+    TrailingInput,
+}
+
+/// An error returned when reading a malformed S-expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset in the input where the error was found.
+    pub position: usize,
+    /// What went wrong.
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self.kind {
+            ParseErrorKind::UnexpectedEof => "unexpected end of input",
+            ParseErrorKind::UnexpectedCloseParen => "unexpected `)`",
+            ParseErrorKind::EmptyList => "empty list",
+            ParseErrorKind::ExpectedAtom => "expected an atom at the head of the list",
+            ParseErrorKind::UnterminatedString => "unterminated quoted atom",
+            ParseErrorKind::TrailingInput => "unexpected input after the root expression",
+        };
+        write!(f, "{} at position {}", description, self.position)
+    }
+}
+
+impl error::Error for ParseError {}
+
+enum Token {
+    Open,
+    Close,
+    Atom(String),
+}
+
+struct Tokenizer<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn error(&self, position: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError { position, kind }
+    }
+
+    /// Returns the next token with its starting position, or `None` at the end of the input.
+    fn next_token(&mut self) -> Result<Option<(usize, Token)>, ParseError> {
+        let rest = &self.input[self.position..];
+        let trimmed = rest.trim_start();
+        self.position += rest.len() - trimmed.len();
+        let start = self.position;
+
+        let mut chars = trimmed.char_indices();
+        let token = match chars.next() {
+            None => return Ok(None),
+            Some((_, '(')) => {
+                self.position += 1;
+                Token::Open
+            }
+            Some((_, ')')) => {
+                self.position += 1;
+                Token::Close
+            }
+            Some((_, '"')) => {
+                let mut atom = String::new();
+                let mut escaped = false;
+                loop {
+                    match chars.next() {
+                        None => return Err(self.error(start, ParseErrorKind::UnterminatedString)),
+                        Some((_, c)) if escaped => {
+                            atom.push(c);
+                            escaped = false;
+                        }
+                        Some((_, '\\')) => escaped = true,
+                        Some((offset, '"')) => {
+                            self.position += offset + 1;
+                            break;
+                        }
+                        Some((_, c)) => atom.push(c),
+                    }
+                }
+                Token::Atom(atom)
+            }
+            Some(_) => {
+                let end = trimmed
+                    .find(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '"')
+                    .unwrap_or(trimmed.len());
+                self.position += end;
+                Token::Atom(trimmed[..end].to_string())
+            }
+        };
+        Ok(Some((start, token)))
+    }
+}
+
+fn needs_quotes(atom: &str) -> bool {
+    atom.is_empty()
+        || atom
+            .chars()
+            .any(|c| c.is_whitespace() || c == '(' || c == ')' || c == '"' || c == '\\')
+}
+
+fn write_atom(out: &mut String, atom: &str) {
+    if !needs_quotes(atom) {
+        out.push_str(atom);
+        return;
+    }
+    out.push('"');
+    for c in atom.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+}
+
+impl<T> VecTree<T> {
+    /// Read a tree from an S-expression, mapping every atom to a node's data with `f`.
+    ///
+    /// See the [`sexpr` module](./sexpr/index.html) for the format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let tree = VecTree::from_sexpr("(1 (2 4 5) 3)", |atom| atom.parse::<i32>().unwrap()).unwrap();
+    ///
+    /// let root = tree.get_root_index().unwrap();
+    /// let values = tree.descendants(root).map(|node| tree[node]).collect::<Vec<_>>();
+    /// assert_eq!(values, [1, 2, 4, 5, 3]);
+    ///
+    /// let error = VecTree::from_sexpr("(1 (2)", |atom| atom.to_string()).unwrap_err();
+    /// assert_eq!(error.position, 6);
+    /// ```
+    pub fn from_sexpr<F>(input: &str, mut f: F) -> Result<VecTree<T>, ParseError>
+    where
+        F: FnMut(&str) -> T,
+    {
+        let mut tokenizer = Tokenizer { input, position: 0 };
+        let mut tree = VecTree::new();
+        // The nodes of the lists which are still open.
+        let mut open_lists: Vec<Index> = Vec::new();
+        // Set right after a `(`, until the head of the list is read.
+        let mut expecting_head = false;
+
+        while let Some((position, token)) = tokenizer.next_token()? {
+            if open_lists.is_empty() && !expecting_head && tree.root_index.is_some() {
+                return Err(tokenizer.error(position, ParseErrorKind::TrailingInput));
+            }
+            match token {
+                Token::Open => {
+                    if expecting_head {
+                        return Err(tokenizer.error(position, ParseErrorKind::ExpectedAtom));
+                    }
+                    expecting_head = true;
+                }
+                Token::Close => {
+                    if expecting_head {
+                        return Err(tokenizer.error(position, ParseErrorKind::EmptyList));
+                    }
+                    if open_lists.pop().is_none() {
+                        return Err(tokenizer.error(position, ParseErrorKind::UnexpectedCloseParen));
+                    }
+                }
+                Token::Atom(atom) => {
+                    let data = f(&atom);
+                    let node_id = match open_lists.last() {
+                        Some(&parent_id) => tree.insert(data, parent_id),
+                        None => tree.insert_root(data),
+                    };
+                    if expecting_head {
+                        open_lists.push(node_id);
+                        expecting_head = false;
+                    }
+                }
+            }
+        }
+
+        if expecting_head || !open_lists.is_empty() || tree.root_index.is_none() {
+            return Err(tokenizer.error(input.len(), ParseErrorKind::UnexpectedEof));
+        }
+        Ok(tree)
+    }
+
+    /// Write the whole tree as an S-expression, formatting every node's data with `f`.
+    ///
+    /// An empty tree is written as an empty string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vec_tree::VecTree;
+    ///
+    /// let mut tree = VecTree::new();
+    /// let root = tree.insert_root("root");
+    /// let a = tree.insert("a", root);
+    /// tree.insert("x", a);
+    /// tree.insert("y", a);
+    /// tree.insert("b c", root);
+    ///
+    /// assert_eq!(tree.to_sexpr(|data| data.to_string()), r#"(root (a x y) "b c")"#);
+    /// ```
+    pub fn to_sexpr<F>(&self, f: F) -> String
+    where
+        F: FnMut(&T) -> String,
+    {
+        match self.root_index {
+            Some(root_id) => self.subtree_to_sexpr(root_id, f),
+            None => String::new(),
+        }
+    }
+
+    /// Write the subtree rooted at `node_id` as an S-expression, formatting every node's data
+    /// with `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node_id` is not in the tree.
+    pub fn subtree_to_sexpr<F>(&self, node_id: Index, mut f: F) -> String
+    where
+        F: FnMut(&T) -> String,
+    {
+        let mut out = String::new();
+        let mut need_space = false;
+
+        for edge in self.traverse(node_id) {
+            match edge {
+                NodeEdge::Start(node_id) => {
+                    if need_space {
+                        out.push(' ');
+                    }
+                    if self.has_children(node_id) {
+                        out.push('(');
+                    }
+                    write_atom(&mut out, &f(&self[node_id]));
+                    need_space = true;
+                }
+                NodeEdge::End(node_id) => {
+                    if self.has_children(node_id) {
+                        out.push(')');
+                    }
+                }
+            }
+        }
+        out
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::bool_assert_comparison)]
+
 extern crate vec_tree;
 use vec_tree::VecTree;
 use vec_tree::Index;
@@ -118,7 +120,7 @@ fn index_deleted_item() {
     let mut tree = VecTree::new();
     let idx = tree.insert_root(42);
     tree.remove(idx);
-    tree[idx];
+    let _ = tree[idx];
 }
 
 #[test]
@@ -627,238 +629,122 @@ fn test_fork() {
 
 #[test]
 fn test_merge() {
+    let parse = |input: &str| VecTree::from_sexpr(input, |atom| atom.parse::<i32>().unwrap()).unwrap();
+    let print = |tree: &VecTree<i32>| tree.to_sexpr(|data| data.to_string());
+
     // Test trying to merge when first node not in tree
-    let mut tree = VecTree::new();
-    let missing_node_id = tree.insert_root(0);
+    let mut tree = parse("(1 11)");
+    let real_root = tree.get_root_index().unwrap();
+    let real_child = node_with_value(&tree, 11);
+    let missing_node_id = tree.insert(0, real_root);
     tree.remove(missing_node_id);
-    let real_root = tree.insert_root(1);
-    let real_child = tree.insert(11, real_root);
-    let node_count = tree.descendants(real_root)
-        .collect::<Vec<Index>>()
-        .len();
-    assert_eq!(2, node_count);
-    tree.merge(missing_node_id, real_child);
-    assert_eq!(node_count,
-               tree.descendants(real_root)
-                   .collect::<Vec<Index>>()
-                   .len()
-    );
+    assert!(tree.merge(missing_node_id, real_child).is_none());
+    assert_eq!(print(&tree), "(1 11)");
 
     // Test trying to merge when second node not in tree
-    tree.merge(real_child, missing_node_id);
-    assert_eq!(node_count,
-               tree.descendants(real_root)
-                   .collect::<Vec<Index>>()
-                   .len()
-    );
+    assert!(tree.merge(real_child, missing_node_id).is_none());
+    assert_eq!(print(&tree), "(1 11)");
 
     // Test trying to merge when don't have same parent
     let real_child_sibling = tree.insert(12, real_root);
-    let node_count = tree.descendants(real_root)
-        .collect::<Vec<Index>>()
-        .len();
-    assert_eq!(3, node_count);
-    tree.merge(real_root, real_child);
-    assert_eq!(node_count,
-               tree.descendants(real_root)
-                   .collect::<Vec<Index>>()
-                   .len()
-    );
+    assert!(tree.merge(real_root, real_child).is_none());
+    assert_eq!(print(&tree), "(1 11 12)");
 
     // Test trying to merge a node with itself
-    tree.merge(real_child, real_child);
-    assert_eq!(node_count,
-               tree.descendants(real_root)
-                   .collect::<Vec<Index>>()
-                   .len()
-    );
+    assert!(tree.merge(real_child, real_child).is_none());
+    assert_eq!(print(&tree), "(1 11 12)");
 
     // Test merging two nodes without children and without siblings
     tree.merge(real_child, real_child_sibling);
+    assert_eq!(print(&tree), "12");
     assert_ne!(Some(real_root), tree.get_root_index());
-    let new_root = tree.get_root_index().unwrap();
-    assert_eq!(1,
-               tree.descendants(new_root)
-                   .collect::<Vec<Index>>()
-                   .len()
-    );
     assert_eq!(false, tree.contains(real_child));
     assert_eq!(None, tree.parent(real_child));
 
-    tree.clear();
-    let root_id = tree.insert_root(1);
-    let parent_id = tree.insert(11, root_id);
-    let _uncle_id = tree.insert(12, root_id);
-    let child_1_id = tree.insert(111, parent_id);
-    let child_2_id = tree.insert(112, parent_id);
-    /*+ for debugging
-    println!("*******BEFORE MERGE:************");
-    for &node_id in &[ root_id, parent_id, uncle_id, child_1_id, child_2_id] {
-        if !tree.contains(node_id) {
-            println!("node {:#?} ABSENT", node_id);
-        } else {
-            println!("node {:#?}:\n{:#?}",node_id, tree.nodes[node_id]);
-        }
-    }
-    */
+    // Test merging two leaves which are the only children of their parent
+    let mut tree = parse("(1 (11 111 112) 12)");
+    let root_id = tree.get_root_index().unwrap();
+    let child_1_id = node_with_value(&tree, 111);
+    let child_2_id = node_with_value(&tree, 112);
     tree.merge(child_1_id, child_2_id);
     assert_eq!(Some(root_id), tree.get_root_index());
-    /*+ for debugging
-    println!("*******AFTER MERGE:************");
-    for &node_id in &[ root_id, parent_id, uncle_id, child_1_id, child_2_id] {
-        if !tree.contains(node_id) {
-            println!("node {:#?} ABSENT", node_id);
-        } else {
-            println!("node {:#?}:\n{:#?}",node_id, tree.nodes[node_id]);
-        }
-    }
-    */
-    println!("About to descend...");
-    assert_eq!(3,
-               tree.descendants(root_id)
-                   .collect::<Vec<Index>>()
-                   .len()
-    );
+    assert_eq!(print(&tree), "(1 112 12)");
 
     // Test merging two nodes where only the first has children
-    tree.clear();
-    let root_id = tree.insert_root(1);
-    let parent_id = tree.insert(11, root_id);
-    let uncle_id = tree.insert(12, root_id);
-    let child_1_id = tree.insert(111, parent_id);
-    let child_2_id = tree.insert(112, parent_id);
+    let mut tree = parse("(1 (11 111 112) 12)");
+    let root_id = tree.get_root_index().unwrap();
+    let parent_id = node_with_value(&tree, 11);
+    let uncle_id = node_with_value(&tree, 12);
+    let child_1_id = node_with_value(&tree, 111);
+    let child_2_id = node_with_value(&tree, 112);
     tree.merge(parent_id, uncle_id);
-    let new_root = tree.get_root_index().unwrap();
-    assert_ne!(root_id, new_root);
-    assert_eq!(uncle_id, new_root);
+    assert_eq!(print(&tree), "(12 111 112)");
+    assert_ne!(Some(root_id), tree.get_root_index());
+    assert_eq!(Some(uncle_id), tree.get_root_index());
     assert_eq!(Some(uncle_id), tree.parent(child_1_id));
-    /*+ for debugging
-    for &node_id in &[ root_id, parent_id, uncle_id, child_1_id, child_2_id] {
-        if !tree.contains(node_id) {
-            println!("node {:#?} ABSENT", node_id);
-        } else {
-            println!("node {:#?}:\n{:#?}",node_id, tree.nodes[node_id]);
-        }
-    }
-    */
     assert_eq!(Some(uncle_id), tree.parent(child_2_id));
 
-
     // Test merging where only the second has children
-    tree.clear();
-    let root_id = tree.insert_root(1);
-    let auntie_id = tree.insert(11, root_id);
-    let parent_id = tree.insert(12, root_id);
-    let child_1_id = tree.insert(121, parent_id);
-    let child_2_id = tree.insert(122, parent_id);
+    let mut tree = parse("(1 11 (12 121 122))");
+    let root_id = tree.get_root_index().unwrap();
+    let auntie_id = node_with_value(&tree, 11);
+    let parent_id = node_with_value(&tree, 12);
+    let child_1_id = node_with_value(&tree, 121);
+    let child_2_id = node_with_value(&tree, 122);
     tree.merge(parent_id, auntie_id);
-    /*+ for debugging
-    for &node_id in &[ root_id, auntie_id, parent_id, child_1_id, child_2_id] {
-        if !tree.contains(node_id) {
-            println!("node {:#?} ABSENT", node_id);
-        } else {
-            println!("node {:#?}:\n{:#?}",node_id, tree.nodes[node_id]);
-        }
-    }
-    */
-    let new_root = tree.get_root_index().unwrap();
-    assert_ne!(root_id, new_root);
-    assert_eq!(auntie_id, new_root);
+    assert_eq!(print(&tree), "(11 121 122)");
+    assert_ne!(Some(root_id), tree.get_root_index());
+    assert_eq!(Some(auntie_id), tree.get_root_index());
     assert_eq!(Some(auntie_id), tree.parent(child_1_id));
     assert_eq!(Some(auntie_id), tree.parent(child_2_id));
 
     // Test merging where both have children
-    tree.clear();
-    let root = tree.insert_root(1000);
-    let gp1 = tree.insert(1100, root);
-    let _gp2 = tree.insert(1200, root);
-    let p1 = tree.insert(1110, gp1);
-    let p2 = tree.insert(1120, gp1);
-    let c11 = tree.insert(1111, p1);
-    let c12 = tree.insert(1112, p1);
-    let c21 = tree.insert(1121, p2);
-    let c22 = tree.insert(1122, p2);
+    let mut tree = parse("(1000 (1100 (1110 1111 1112) (1120 1121 1122)) 1200)");
+    let root = tree.get_root_index().unwrap();
+    let gp1 = node_with_value(&tree, 1100);
+    let p1 = node_with_value(&tree, 1110);
+    let p2 = node_with_value(&tree, 1120);
     tree.merge(p1, p2);
+    assert_eq!(print(&tree), "(1000 (1120 1111 1112 1121 1122) 1200)");
     assert_eq!(Some(root), tree.get_root_index());
     assert_eq!(false, tree.contains(p1));
     assert_eq!(false, tree.contains(gp1));
-    //+print_nodes(&tree, &[root, gp1, gp2, p1, p2, c11, c12, c21, c22]);
     assert_eq!(Some(root), tree.parent(p2));
-    assert_eq!(Some(p2), tree.parent(c11));
-    let children = tree.children(p2).collect::<Vec<Index>>();
-    let expected_children = vec!(c11, c12, c21, c22);
-    assert_eq!(expected_children, children);
 
     // Test merging where there are siblings before
-    tree.clear();
-    let root = tree.insert_root(1000);
-    let _gp1 = tree.insert(1100, root);
-    let gp2 = tree.insert(1200, root);
-    let _gp3 = tree.insert(1300, root);
-    let p1 = tree.insert(1110, gp2);
-    let p2 = tree.insert(1120, gp2);
-    let p3 = tree.insert(1130, gp2);
-    let c11 = tree.insert(1111, p2);
-    let c12 = tree.insert(1112, p2);
-    let c21 = tree.insert(1121, p3);
-    let c22 = tree.insert(1122, p3);
+    let mut tree = parse("(1000 1100 (1200 1110 (1120 1121 1122) (1130 1131 1132)) 1300)");
+    let gp2 = node_with_value(&tree, 1200);
+    let p2 = node_with_value(&tree, 1120);
+    let p3 = node_with_value(&tree, 1130);
     tree.merge(p2, p3);
     assert_eq!(true, tree.contains(gp2)); // shouldn't collapse.
-    let children = tree.children(gp2).collect::<Vec<Index>>();
-    assert_eq!(vec!(p1,p3), children);
-    let children = tree.children(p3).collect::<Vec<Index>>();
-    assert_eq!(vec!(c11,c12,c21,c22), children);
+    assert_eq!(print(&tree), "(1000 1100 (1200 1110 (1130 1121 1122 1131 1132)) 1300)");
+
     // Test merging where there are siblings between
-    tree.clear();
-    let root = tree.insert_root(1000);
-    let _gp1 = tree.insert(1100, root);
-    let gp2 = tree.insert(1200, root);
-    let _gp3 = tree.insert(1300, root);
-    let p1 = tree.insert(1110, gp2);
-    let p2 = tree.insert(1120, gp2);
-    let p3 = tree.insert(1130, gp2);
-    let c11 = tree.insert(1111, p1);
-    let c12 = tree.insert(1112, p1);
-    let _c21 = tree.insert(1121, p2);
-    let _c22 = tree.insert(1122, p2);
-    let c31 = tree.insert(1121, p3);
-    let c32 = tree.insert(1122, p3);
+    let mut tree = parse("(1000 1100 (1200 (1110 1111 1112) (1120 1121 1122) (1130 1131 1132)) 1300)");
+    let gp2 = node_with_value(&tree, 1200);
+    let p1 = node_with_value(&tree, 1110);
+    let p3 = node_with_value(&tree, 1130);
     tree.merge(p1, p3);
     assert_eq!(true, tree.contains(gp2)); // shouldn't collapse.
-    let children = tree.children(gp2).collect::<Vec<Index>>();
-    assert_eq!(vec!(p2,p3), children);
-    let children = tree.children(p3).collect::<Vec<Index>>();
-    assert_eq!(vec!(c11,c12,c31,c32), children);
+    assert_eq!(print(&tree), "(1000 1100 (1200 (1120 1121 1122) (1130 1111 1112 1131 1132)) 1300)");
 
     // Test merging where there are siblings after
-    tree.clear();
-    let root = tree.insert_root(1000);
-    let _gp1 = tree.insert(1100, root);
-    let gp2 = tree.insert(1200, root);
-    let _gp3 = tree.insert(1300, root);
-    let p1 = tree.insert(1110, gp2);
-    let p2 = tree.insert(1120, gp2);
-    let p3 = tree.insert(1130, gp2);
-    let c11 = tree.insert(1111, p1);
-    let c12 = tree.insert(1112, p1);
-    let c21 = tree.insert(1121, p2);
-    let c22 = tree.insert(1122, p2);
-    let _c31 = tree.insert(1121, p3);
-    let _c32 = tree.insert(1122, p3);
+    let mut tree = parse("(1000 1100 (1200 (1110 1111 1112) (1120 1121 1122) (1130 1131 1132)) 1300)");
+    let gp2 = node_with_value(&tree, 1200);
+    let p1 = node_with_value(&tree, 1110);
+    let p2 = node_with_value(&tree, 1120);
     tree.merge(p1, p2);
     assert_eq!(true, tree.contains(gp2)); // shouldn't collapse.
-    let children = tree.children(gp2).collect::<Vec<Index>>();
-    assert_eq!(vec!(p2,p3), children);
-    let children = tree.children(p2).collect::<Vec<Index>>();
-    assert_eq!(vec!(c11,c12,c21,c22), children);
+    assert_eq!(print(&tree), "(1000 1100 (1200 (1120 1111 1112 1121 1122) (1130 1131 1132)) 1300)");
 
     // Test merging where the parent is root
-    tree.clear();
-    let root = tree.insert_root(10);
-    let c1 = tree.insert(11,root);
-    let c2 = tree.insert(12, root);
+    let mut tree = parse("(10 11 12)");
+    let root = tree.get_root_index().unwrap();
+    let c1 = node_with_value(&tree, 11);
+    let c2 = node_with_value(&tree, 12);
     tree.merge(c1, c2);
-    assert_ne!(Some(root), tree.get_root_index());
+    assert_eq!(print(&tree), "12");
     assert_eq!(Some(c2), tree.get_root_index());
     assert_eq!(false, tree.contains(root));
     assert_eq!(false, tree.contains(c1));
@@ -893,3 +779,1375 @@ fn test_ancestors() {
     print_nodes(&tree, &[new_root, root, new_sibling]);
     assert_eq!(None, tree.parent(new_sibling));
 }
+
+// Look up the node holding `value`, for trees built from fixtures.
+fn node_with_value<T>(tree: &VecTree<T>, value: T) -> Index
+    where
+        T: PartialEq
+{
+    let root = tree.get_root_index().unwrap();
+    tree.descendants(root).find(|&node_id| tree[node_id] == value).unwrap()
+}
+
+#[test]
+fn test_sexpr() {
+    let tree = VecTree::from_sexpr("(root (a x y) (b))", |atom| atom.to_string()).unwrap();
+    let root = tree.get_root_index().unwrap();
+    assert_eq!(tree[root], "root");
+    let children = tree.children(root).map(|node_id| tree[node_id].clone()).collect::<Vec<_>>();
+    assert_eq!(children, ["a", "b"]);
+    assert_eq!(tree.to_sexpr(|data| data.clone()), "(root (a x y) b)");
+
+    let a = node_with_value(&tree, "a".to_string());
+    assert_eq!(tree.subtree_to_sexpr(a, |data| data.to_uppercase()), "(A X Y)");
+
+    // Quoted atoms round-trip.
+    let tree = VecTree::from_sexpr(r#"("a b" "" "c\"d" e\f)"#, |atom| atom.to_string()).unwrap();
+    let root = tree.get_root_index().unwrap();
+    let values = tree.descendants(root).map(|node_id| tree[node_id].clone()).collect::<Vec<_>>();
+    assert_eq!(values, ["a b", "", "c\"d", "e\\f"]);
+    assert_eq!(tree.to_sexpr(|data| data.clone()), r#"("a b" "" "c\"d" "e\\f")"#);
+
+    // A single atom is a tree with only a root.
+    let tree = VecTree::from_sexpr("  42 ", |atom| atom.parse::<i32>().unwrap()).unwrap();
+    assert_eq!(tree.to_sexpr(|data| data.to_string()), "42");
+
+    assert_eq!(VecTree::<i32>::new().to_sexpr(|data| data.to_string()), "");
+}
+
+#[test]
+fn test_sexpr_errors() {
+    use vec_tree::sexpr::ParseErrorKind;
+
+    let kind_and_position = |input: &str| {
+        let error = VecTree::from_sexpr(input, |atom| atom.to_string()).unwrap_err();
+        (error.kind, error.position)
+    };
+    assert_eq!((ParseErrorKind::UnexpectedEof, 0), kind_and_position(""));
+    assert_eq!((ParseErrorKind::UnexpectedEof, 7), kind_and_position("(a (b c"));
+    assert_eq!((ParseErrorKind::UnexpectedEof, 1), kind_and_position("("));
+    assert_eq!((ParseErrorKind::UnexpectedCloseParen, 0), kind_and_position(")"));
+    assert_eq!((ParseErrorKind::EmptyList, 4), kind_and_position("(a ()"));
+    assert_eq!((ParseErrorKind::ExpectedAtom, 1), kind_and_position("((a) b)"));
+    assert_eq!((ParseErrorKind::UnterminatedString, 3), kind_and_position("(a \"b)"));
+    assert_eq!((ParseErrorKind::TrailingInput, 4), kind_and_position("(a) b"));
+    assert_eq!((ParseErrorKind::TrailingInput, 2), kind_and_position("a (b)"));
+}
+
+#[test]
+fn test_newick() {
+    use vec_tree::newick::{NewickData, NewickNode};
+
+    let tree = VecTree::<NewickNode>::from_newick(
+        " ( [a comment] (A:0.1, B_b :2e-1)'E''s' : 0.5,'C d':3,) [&&NHX:S=x] F ; ",
+    )
+    .unwrap();
+    let root = tree.get_root_index().unwrap();
+    let nodes = tree
+        .descendants(root)
+        .map(|node_id| (tree[node_id].label.clone(), tree[node_id].branch_length))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        nodes,
+        [
+            (Some("F".to_string()), None),
+            (Some("E's".to_string()), Some(0.5)),
+            (Some("A".to_string()), Some(0.1)),
+            (Some("B b".to_string()), Some(0.2)),
+            (Some("C d".to_string()), Some(3.0)),
+            (None, None),
+        ]
+    );
+    assert_eq!(tree.to_newick(), "((A:0.1,'B b':0.2)'E''s':0.5,'C d':3,)F;");
+
+    // Writing and reading back gives the same tree.
+    let written = tree.to_newick();
+    let read_back = VecTree::<NewickNode>::from_newick(&written).unwrap();
+    assert_eq!(read_back.to_newick(), written);
+
+    let tree = VecTree::<NewickNode>::from_newick("A;").unwrap();
+    assert_eq!(tree.to_newick(), "A;");
+    let tree = VecTree::<NewickNode>::from_newick("(,(,));").unwrap();
+    assert_eq!(tree.to_newick(), "(,(,));");
+    assert_eq!(VecTree::<NewickNode>::new().to_newick(), "");
+
+    // Non-finite branch lengths round-trip.
+    let tree = VecTree::<NewickNode>::from_newick("(A:inf,B:-inf,C:NaN);").unwrap();
+    let root = tree.get_root_index().unwrap();
+    let lengths = tree.children(root).map(|node_id| tree[node_id].branch_length.unwrap()).collect::<Vec<_>>();
+    assert_eq!(lengths[..2], [f64::INFINITY, f64::NEG_INFINITY]);
+    assert!(lengths[2].is_nan());
+    assert_eq!(tree.to_newick(), "(A:inf,B:-inf,C:NaN);");
+    assert_eq!(VecTree::<NewickNode>::from_newick(&tree.to_newick()).unwrap().to_newick(), tree.to_newick());
+
+    // The data of every node is built once, with its own label.
+    struct Labelled(String);
+
+    impl NewickData for Labelled {
+        fn from_newick(label: Option<String>, _: Option<f64>) -> Self {
+            Labelled(label.expect("every node has a label"))
+        }
+
+        fn newick_label(&self) -> Option<&str> {
+            Some(&self.0)
+        }
+
+        fn newick_branch_length(&self) -> Option<f64> {
+            None
+        }
+    }
+
+    let tree = VecTree::<Labelled>::from_newick("((A,B)E,(C)D)F;").unwrap();
+    assert_eq!(tree.len(), 6);
+    assert_eq!(tree.to_newick(), "((A,B)E,(C)D)F;");
+}
+
+#[test]
+fn test_newick_errors() {
+    use vec_tree::newick::{NewickNode, ParseErrorKind};
+
+    let kind_and_position = |input: &str| {
+        let error = VecTree::<NewickNode>::from_newick(input).unwrap_err();
+        (error.kind, error.position)
+    };
+    assert_eq!((ParseErrorKind::UnexpectedEof, 0), kind_and_position(""));
+    assert_eq!((ParseErrorKind::UnexpectedEof, 5), kind_and_position("(A,B)"));
+    assert_eq!((ParseErrorKind::UnexpectedEof, 4), kind_and_position("(A,B"));
+    assert_eq!((ParseErrorKind::UnexpectedCharacter(')'), 1), kind_and_position("A);"));
+    assert_eq!((ParseErrorKind::UnexpectedCharacter(','), 1), kind_and_position("A,B;"));
+    assert_eq!((ParseErrorKind::UnexpectedCharacter('('), 2), kind_and_position("(A(B));"));
+    assert_eq!((ParseErrorKind::UnterminatedQuote, 1), kind_and_position("('A,B);"));
+    assert_eq!((ParseErrorKind::UnterminatedComment, 3), kind_and_position("(A,[B);"));
+    assert_eq!((ParseErrorKind::InvalidBranchLength, 4), kind_and_position("(A: x,B);"));
+    assert_eq!((ParseErrorKind::TrailingInput, 7), kind_and_position("(A,B); C;"));
+}
+
+#[test]
+fn test_from_nested() {
+    // (value, children) pairs
+    struct Nested(i32, Vec<Nested>);
+
+    let nested = Nested(1, vec![Nested(2, vec![Nested(4, vec![]), Nested(5, vec![])]), Nested(3, vec![])]);
+    let tree = VecTree::from_nested(nested, |Nested(value, children)| (value, children));
+    assert_eq!(tree.to_sexpr(|data| data.to_string()), "(1 (2 4 5) 3)");
+
+    // Deep nesting doesn't overflow the stack.
+    let tree = VecTree::from_nested(0, |depth| (depth, if depth < 100_000 { Some(depth + 1) } else { None }));
+    let leaf = node_with_value(&tree, 100_000);
+    assert_eq!(tree.ancestors(leaf).count(), 100_001);
+}
+
+#[test]
+fn test_tree_macro() {
+    let tree = vec_tree::tree![1 => [2 => [4, 5,], 3 => [], 6], ];
+    assert_eq!(tree.to_sexpr(|data| data.to_string()), "(1 (2 4 5) 3 6)");
+
+    let tree = vec_tree::tree!["root"];
+    assert_eq!(tree.to_sexpr(|data| data.to_string()), "root");
+
+    let tree = vec_tree::tree![(1, 2) => [(3, 4)]];
+    assert_eq!(tree.to_sexpr(|&(a, b)| format!("{}-{}", a, b)), "(1-2 3-4)");
+}
+
+#[test]
+fn test_from_parent_list() {
+    use vec_tree::FromParentListError;
+
+    let tree = VecTree::from_parent_list(vec![
+        (Some(3), 2),
+        (Some(0), 4),
+        (Some(3), 3),
+        (None, 1),
+        (Some(0), 5),
+    ])
+    .unwrap();
+    assert_eq!(tree.to_sexpr(|data| data.to_string()), "(1 (2 4 5) 3)");
+
+    let error = |list: Vec<(Option<usize>, i32)>| VecTree::from_parent_list(list).unwrap_err();
+    assert_eq!(FromParentListError::NoRoot, error(vec![]));
+    assert_eq!(FromParentListError::NoRoot, error(vec![(Some(1), 0), (Some(0), 1)]));
+    assert_eq!(FromParentListError::MultipleRoots(0, 2), error(vec![(None, 0), (Some(0), 1), (None, 2)]));
+    assert_eq!(
+        FromParentListError::ParentOutOfBounds { node: 1, parent: 5 },
+        error(vec![(None, 0), (Some(5), 1)])
+    );
+    assert_eq!(FromParentListError::Cycle(1), error(vec![(None, 0), (Some(1), 1)]));
+    assert_eq!(FromParentListError::Cycle(1), error(vec![(None, 0), (Some(2), 1), (Some(3), 2), (Some(1), 3)]));
+}
+
+#[test]
+fn test_structural_equality() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let hash = |tree: &VecTree<i32>| {
+        let mut hasher = DefaultHasher::new();
+        tree.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let original = vec_tree::tree![1 => [2 => [4, 5], 3]];
+    assert_eq!(original, original.clone());
+
+    // Fork followed by merge gives back the original tree.
+    let mut tree = original.clone();
+    let node_2 = node_with_value(&tree, 2);
+    let new_sibling = tree.fork(node_2, 10, 20).unwrap();
+    assert_ne!(original, tree);
+    tree.merge(new_sibling, node_2);
+    assert_eq!(original, tree);
+    assert_eq!(hash(&original), hash(&tree));
+
+    // The layout in the arena doesn't matter.
+    let mut tree = VecTree::with_capacity(10);
+    let root = tree.insert_root(1);
+    let garbage = tree.insert(99, root);
+    let node_3 = tree.insert(3, root);
+    tree.remove(garbage);
+    let node_2 = tree.insert(2, root);
+    tree.insert(5, node_2);
+    tree.insert(4, node_2);
+    tree.append_child(node_2, node_with_value(&tree, 5));
+    tree.append_child(root, node_3);
+    assert_eq!(original, tree);
+    assert_eq!(hash(&original), hash(&tree));
+
+    // But the order of the children does.
+    tree.append_child(node_2, node_with_value(&tree, 4));
+    assert_ne!(original, tree);
+    assert!(original < tree);
+
+    assert_eq!(VecTree::<i32>::new(), VecTree::new());
+    assert_ne!(VecTree::new(), original);
+    assert!(VecTree::new() < original);
+
+    // Fewer children come first.
+    assert!(vec_tree::tree![1 => [2]] < vec_tree::tree![1 => [2, 3]]);
+    assert!(vec_tree::tree![1 => [2 => [9]]] > vec_tree::tree![1 => [2, 3]]);
+    assert!(vec_tree::tree![1 => [3]] > vec_tree::tree![1 => [2, 3]]);
+    assert_eq!(
+        vec_tree::tree![1.0 => [f64::NAN]].partial_cmp(&vec_tree::tree![1.0 => [2.0]]),
+        None
+    );
+
+    let tree = vec_tree::tree![1 => [2 => [4], 2 => [4], 2]];
+    let children = tree.children(tree.get_root_index().unwrap()).collect::<Vec<_>>();
+    assert!(tree.subtree_eq(children[0], &tree, children[1]));
+    assert!(!tree.subtree_eq(children[0], &tree, children[2]));
+    assert!(!tree.subtree_eq(children[0], &original, node_with_value(&original, 2)));
+}
+
+#[test]
+fn test_diff_and_apply_patch() {
+    use vec_tree::diff::Edit;
+
+    let parse = |input: &str| {
+        if input.is_empty() {
+            VecTree::new()
+        } else {
+            VecTree::from_sexpr(input, |atom| atom.to_string()).unwrap()
+        }
+    };
+    let check = |from: &str, to: &str| {
+        let mut tree = parse(from);
+        let other = parse(to);
+        let patch = tree.diff(&other, |data| data.clone());
+        tree.apply_patch(patch.clone());
+        assert_eq!(tree.to_sexpr(|data| data.clone()), other.to_sexpr(|data| data.clone()));
+        patch
+    };
+
+    assert!(check("(a (b c d) e)", "(a (b c d) e)").is_empty());
+    assert!(check("", "").is_empty());
+    check("", "(a b c)");
+    check("(a b c)", "");
+
+    // Reordering siblings
+    check("(a b c d e)", "(a e d c b)");
+    check("(a b c d e)", "(a c d e b)");
+    // Moving between parents, deeper and shallower
+    check("(a (b c d) (e f))", "(a (e d (f c)) b)");
+    check("(a (b (c (d e))))", "(a e d c b)");
+    check("(a b c d)", "(a (b (c (d))))");
+    // Removing a subtree with nodes to keep in it
+    check("(a (b (c d e) f) g)", "(a g e d)");
+    // Replacing the root
+    check("(a b c)", "(x b c)");
+    check("(a (b c d))", "(b (a c) d)");
+    check("(a (b c d))", "(c d (x a b))");
+    // Duplicate keys
+    check("(a b b (c b))", "(a b (c b b) b)");
+
+    // Only the topmost node of a removed subtree is removed.
+    let patch = check("(a (b c d) e)", "(a e)");
+    let removed = patch
+        .iter()
+        .filter(|edit| matches!(edit, Edit::Remove { .. }))
+        .count();
+    assert_eq!(removed, 1);
+
+    // Updates
+    let mut tree = vec_tree::tree![(1, "a") => [(2, "b"), (3, "c")]];
+    let other = vec_tree::tree![(1, "a") => [(3, "C"), (4, "d") => [(2, "B")]]];
+    let patch = tree.diff(&other, |&(key, _)| key);
+    let inserted = tree.apply_patch(patch);
+    assert_eq!(tree, other);
+    assert_eq!(inserted.len(), 1);
+    assert_eq!(tree[inserted[0]], (4, "d"));
+}
+
+#[test]
+fn test_edit_distance() {
+    let parse = |input: &str| VecTree::from_sexpr(input, |atom| atom.to_string()).unwrap();
+    let unit_distance = |from: &str, to: &str| {
+        let tree = parse(from);
+        let other = parse(to);
+        tree.edit_distance(
+            tree.get_root_index().unwrap(),
+            &other,
+            other.get_root_index().unwrap(),
+            |_| 1.0,
+            |_| 1.0,
+            |a, b| if a == b { 0.0 } else { 1.0 },
+        )
+    };
+
+    assert_eq!(unit_distance("(a b c)", "(a b c)").distance, 0.0);
+    assert_eq!(unit_distance("(a b c)", "(a b)").distance, 1.0);
+    assert_eq!(unit_distance("(a b c)", "(a c b)").distance, 2.0);
+    assert_eq!(unit_distance("(a b c)", "(x b c)").distance, 1.0);
+    assert_eq!(unit_distance("a", "(a b (c d e))").distance, 4.0);
+    // Deleting "b" lifts its children.
+    assert_eq!(unit_distance("(a (b c d) e)", "(a c d e)").distance, 1.0);
+    assert_eq!(unit_distance("(a (b c d) e)", "(a (x c d) e)").distance, 1.0);
+
+    // The mapping keeps everything but the deleted node, in post-order.
+    let tree = parse("(a (b c d) e)");
+    let other = parse("(a c d e)");
+    let result = tree.edit_distance(
+        tree.get_root_index().unwrap(),
+        &other,
+        other.get_root_index().unwrap(),
+        |_| 1.0,
+        |_| 1.0,
+        |a, b| if a == b { 0.0 } else { 1.0 },
+    );
+    let mapping = result
+        .mapping
+        .iter()
+        .map(|&(a, b)| (tree[a].as_str(), other[b].as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(mapping, [("c", "c"), ("d", "d"), ("e", "e"), ("a", "a")]);
+
+    // Costs are taken into account: here relabelling is cheaper than deleting and inserting.
+    let tree = vec_tree::tree![1 => [2 => [3]]];
+    let other = vec_tree::tree![1.0 => [20.0 => [3.0]]];
+    let node_2 = node_with_value(&tree, 2);
+    let result = tree.edit_distance(
+        node_2,
+        &other,
+        other.children(other.get_root_index().unwrap()).next().unwrap(),
+        |_| 10.0,
+        |_| 10.0,
+        |&a, &b| (a as f64 - b).abs(),
+    );
+    assert_eq!(result.distance, 18.0);
+    assert_eq!(result.mapping.len(), 2);
+}
+
+#[test]
+fn test_compact() {
+    let mut tree = VecTree::new();
+    let root = tree.insert_root(0);
+    let mut nodes = vec![root];
+    for i in 1..200 {
+        let parent = nodes[(i * 7) % nodes.len()];
+        nodes.push(tree.insert(i, parent));
+    }
+    for &node_id in nodes.iter().skip(3).step_by(5) {
+        tree.remove(node_id);
+    }
+    let before = tree.clone();
+    let remaining = nodes.iter().filter(|&&node_id| tree.contains(node_id)).count();
+
+    let remap = tree.compact();
+    assert_eq!(tree, before);
+    assert_eq!(remap.len(), remaining);
+    assert_eq!(tree.capacity(), remaining);
+
+    // The nodes are laid out in tree order.
+    let root = tree.get_root_index().unwrap();
+    assert_eq!(root, remap[nodes[0]]);
+    let order = tree.descendants(root).collect::<Vec<_>>();
+    assert!(order.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // Old indices map to the nodes holding the same data.
+    for (old_id, new_id) in remap.iter() {
+        assert_eq!(before[old_id], tree[new_id]);
+    }
+    for &node_id in &nodes {
+        assert_eq!(before.contains(node_id), remap.get(node_id).is_some());
+    }
+
+    // Old indices are not valid anymore, even once new nodes are inserted.
+    for _ in 0..200 {
+        tree.insert(1000, root);
+    }
+    for &node_id in &nodes {
+        assert_eq!(tree.get(node_id), None);
+        assert!(!tree.contains(node_id));
+    }
+
+    let mut empty = VecTree::<i32>::with_capacity(10);
+    assert!(empty.compact().is_empty());
+    assert_eq!(empty.get_root_index(), None);
+}
+
+#[test]
+fn test_len_and_memory_stats() {
+    let mut tree = VecTree::with_capacity(10);
+    assert_eq!(tree.len(), 0);
+    assert!(tree.is_empty());
+
+    let root = tree.insert_root(0);
+    let child = tree.insert(1, root);
+    tree.insert(2, child);
+    tree.insert(3, root);
+    assert_eq!(tree.len(), 4);
+    assert!(!tree.is_empty());
+
+    let stats = tree.memory_stats();
+    assert_eq!(stats.live_nodes, 4);
+    assert_eq!(stats.free_slots, 6);
+    assert_eq!(stats.fragmentation, 0.6);
+
+    // Removing a node removes its descendants too.
+    tree.remove(child);
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree.memory_stats().free_slots, 8);
+
+    // The free slots after the last node are released, not those before.
+    tree.shrink_to_fit();
+    assert_eq!(tree.capacity(), 4);
+    assert_eq!(tree.memory_stats().free_slots, 2);
+    tree.remove(root);
+    tree.shrink_to_fit();
+    assert_eq!(tree.capacity(), 0);
+    assert_eq!(tree.memory_stats().fragmentation, 0.0);
+
+    // The indices of the removed nodes stay invalid.
+    let old_root = root;
+    let root = tree.insert_root(5);
+    tree.insert(6, root);
+    assert_eq!(tree.len(), 2);
+    assert_ne!(root, old_root);
+    assert_eq!(tree.get(old_root), None);
+    assert!(!tree.contains(child));
+
+    // The data of the remaining nodes is moved into the slots left free.
+    let mut tree = VecTree::new();
+    let root = tree.insert_root(0);
+    let nodes = (1..100).map(|i| tree.insert(i, root)).collect::<Vec<_>>();
+    for &node_id in nodes.iter().step_by(3) {
+        tree.remove(node_id);
+    }
+    let before = tree.clone();
+    tree.shrink_to_fit();
+    assert_eq!(tree, before);
+    for &node_id in &nodes {
+        assert_eq!(tree.get(node_id), before.get(node_id));
+    }
+    let node = tree.insert(100, nodes[1]);
+    assert_eq!(tree[node], 100);
+    assert_eq!(tree[nodes[1]], 2);
+}
+
+#[test]
+fn test_shrink_to_fit_keeps_indices() {
+    let mut tree = VecTree::with_capacity(20);
+    let root = tree.insert_root(0);
+    let nodes = (1..10).map(|i| tree.insert(i, root)).collect::<Vec<_>>();
+    for &node_id in &nodes[5..] {
+        tree.remove(node_id);
+    }
+    tree.remove(nodes[2]);
+
+    let before = tree.clone();
+    tree.shrink_to_fit();
+    assert_eq!(tree.capacity(), 6);
+    assert_eq!(tree, before);
+    for &node_id in &nodes {
+        assert_eq!(tree.get(node_id), before.get(node_id));
+    }
+
+    // The slot left free between nodes is used first, then the tree grows back,
+    // without the indices of the removed nodes designating the new ones.
+    let new_nodes = (10..15).map(|i| tree.insert(i, nodes[0])).collect::<Vec<_>>();
+    for &node_id in nodes[5..].iter().chain(&nodes[2..3]) {
+        assert!(!tree.contains(node_id));
+    }
+    for (i, &node_id) in (10..15).zip(&new_nodes) {
+        assert_eq!(tree[node_id], i);
+    }
+    assert_eq!(tree.children(nodes[0]).count(), 5);
+    assert_eq!(tree.len(), 10);
+}
+
+#[test]
+fn test_remove_subtree_with() {
+    let mut tree = VecTree::new();
+    let root = tree.insert_root(1);
+    let a = tree.insert(2, root);
+    let b = tree.insert(3, a);
+    tree.insert(4, b);
+    tree.insert(5, b);
+    tree.insert(6, a);
+    let c = tree.insert(7, root);
+
+    let mut removed = Vec::new();
+    assert_eq!(tree.remove_subtree_with(a, |index, data| removed.push((index, data))), Some(2));
+    assert_eq!(removed.iter().map(|&(_, data)| data).collect::<Vec<_>>(), [4, 5, 3, 6]);
+    assert!(removed.iter().all(|&(index, _)| !tree.contains(index)));
+    assert!(!tree.contains(a));
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree.children(root).collect::<Vec<_>>(), [c]);
+
+    // Removing a node that was already removed does nothing.
+    assert_eq!(tree.remove_subtree_with(a, |_, _| panic!("nothing to remove")), None);
+
+    let mut count = 0;
+    assert_eq!(tree.remove_subtree_with(root, |_, _| count += 1), Some(1));
+    assert_eq!(count, 1);
+    assert!(tree.is_empty());
+    assert_eq!(tree.get_root_index(), None);
+}
+
+#[test]
+fn test_drain_and_take_subtree() {
+    use vec_tree::DrainOrder;
+
+    let mut tree = VecTree::from_sexpr("(1 (2 (3 4 5) 6) 7)", |atom| atom.to_string()).unwrap();
+    let two = node_with_value(&tree, "2".to_string());
+
+    let drained = tree.drain_subtree(two, DrainOrder::PreOrder).collect::<Vec<_>>();
+    let expected = [(0, "2"), (1, "3"), (2, "4"), (2, "5"), (1, "6")];
+    assert_eq!(drained.iter().map(|(depth, data)| (*depth, data.as_str())).collect::<Vec<_>>(), expected);
+    assert_eq!(tree.to_sexpr(|data| data.clone()), "(1 7)");
+
+    // The nodes are removed even if the iterator isn't consumed.
+    let mut tree = VecTree::from_sexpr("(1 (2 (3 4 5) 6) 7)", |atom| atom.to_string()).unwrap();
+    let two = node_with_value(&tree, "2".to_string());
+    let mut drain = tree.drain_subtree(two, DrainOrder::PostOrder);
+    assert_eq!(drain.next(), Some((2, "4".to_string())));
+    assert_eq!(drain.next(), Some((2, "5".to_string())));
+    drop(drain);
+    assert_eq!(tree.len(), 2);
+    assert!(!tree.contains(two));
+    assert_eq!(tree.drain_subtree(two, DrainOrder::PostOrder).next(), None);
+
+    let mut tree = VecTree::from_sexpr("(1 (2 (3 4 5) 6) 7)", |atom| atom.to_string()).unwrap();
+    let three = node_with_value(&tree, "3".to_string());
+    let subtree = tree.take_subtree(three).unwrap();
+    assert_eq!(subtree.to_sexpr(|data| data.clone()), "(3 4 5)");
+    assert_eq!(tree.to_sexpr(|data| data.clone()), "(1 (2 6) 7)");
+    assert!(tree.take_subtree(three).is_none());
+
+    let root_id = tree.get_root_index().unwrap();
+    let whole = tree.take_subtree(root_id).unwrap();
+    assert_eq!(whole.to_sexpr(|data| data.clone()), "(1 (2 6) 7)");
+    assert!(tree.is_empty());
+    assert_eq!(tree.get_root_index(), None);
+}
+
+#[test]
+fn test_remove_and_lift_children_and_wrap() {
+    let sexpr = |tree: &VecTree<String>| tree.to_sexpr(|data| data.clone());
+    let mut tree = VecTree::from_sexpr("(1 (2 3 (4 5)) 6)", |atom| atom.to_string()).unwrap();
+
+    let two = node_with_value(&tree, "2".to_string());
+    assert_eq!(tree.remove_and_lift_children(two), Some("2".to_string()));
+    assert_eq!(sexpr(&tree), "(1 3 (4 5) 6)");
+    assert_eq!(tree.remove_and_lift_children(two), None);
+
+    // Lifting the children of a leaf just removes it.
+    let six = node_with_value(&tree, "6".to_string());
+    assert_eq!(tree.remove_and_lift_children(six), Some("6".to_string()));
+    assert_eq!(sexpr(&tree), "(1 3 (4 5))");
+
+    let three = node_with_value(&tree, "3".to_string());
+    let four = node_with_value(&tree, "4".to_string());
+    let wrapper = tree.wrap(three, four, "2".to_string()).unwrap();
+    assert_eq!(sexpr(&tree), "(1 (2 3 (4 5)))");
+    assert_eq!(tree.parent(three), Some(wrapper));
+    assert_eq!(tree.wrap(four, three, "7".to_string()), None);
+    assert_eq!(tree.wrap(three, node_with_value(&tree, "5".to_string()), "7".to_string()), None);
+
+    // The root can only be lifted away when it has a single child.
+    let root = tree.get_root_index().unwrap();
+    assert_eq!(tree.remove_and_lift_children(root), Some("1".to_string()));
+    assert_eq!(tree.get_root_index(), Some(wrapper));
+    assert_eq!(tree.parent(wrapper), None);
+    assert_eq!(tree.remove_and_lift_children(wrapper), None);
+    assert_eq!(sexpr(&tree), "(2 3 (4 5))");
+
+    let new_root = tree.wrap(wrapper, wrapper, "0".to_string()).unwrap();
+    assert_eq!(tree.get_root_index(), Some(new_root));
+    assert_eq!(sexpr(&tree), "(0 (2 3 (4 5)))");
+
+    let mut tree = VecTree::new();
+    let root = tree.insert_root(1);
+    assert_eq!(tree.remove_and_lift_children(root), Some(1));
+    assert!(tree.is_empty());
+    assert_eq!(tree.get_root_index(), None);
+}
+
+#[test]
+fn test_fork_last_child_keeps_parent_links() {
+    // Forking the last child used to leave it as the last child of its old parent.
+    let mut tree = VecTree::new();
+    let root = tree.insert_root(1);
+    let first = tree.insert(11, root);
+    let last = tree.insert(12, root);
+    let new_sibling = tree.fork(last, 20, 13).unwrap();
+    let new_parent = tree.parent(last).unwrap();
+
+    let appended = tree.insert(14, root);
+    assert_eq!(tree.children(root).collect::<Vec<_>>(), [first, new_parent, appended]);
+    assert_eq!(tree.children(new_parent).collect::<Vec<_>>(), [last, new_sibling]);
+}
+
+#[test]
+fn test_fork_many() {
+    let sexpr = |tree: &VecTree<i32>| tree.to_sexpr(|data| data.to_string());
+
+    let mut tree = VecTree::new();
+    let root = tree.insert_root(1);
+    let node = tree.insert(2, root);
+    tree.insert(3, root);
+
+    let (new_parent, new_siblings) = tree.fork_many(node, 10, vec![11, 12], 1).unwrap();
+    assert_eq!(sexpr(&tree), "(1 (10 11 2 12) 3)");
+    assert_eq!(tree.children(new_parent).collect::<Vec<_>>(), [new_siblings[0], node, new_siblings[1]]);
+
+    // A position past the new siblings places the node last.
+    tree.fork_many(node, 20, vec![21], 5).unwrap();
+    assert_eq!(sexpr(&tree), "(1 (10 11 (20 21 2) 12) 3)");
+
+    // Without new siblings, the node just gets a new parent.
+    let (new_parent, new_siblings) = tree.fork_many(node, 30, Vec::new(), 0).unwrap();
+    assert!(new_siblings.is_empty());
+    assert_eq!(tree.children(new_parent).collect::<Vec<_>>(), [node]);
+
+    // Forking the root replaces it.
+    let (new_root, _) = tree.fork_many(root, 0, vec![-1, -2], 2).unwrap();
+    assert_eq!(tree.get_root_index(), Some(new_root));
+    assert_eq!(sexpr(&tree), "(0 -1 -2 (1 (10 11 (20 21 (30 2)) 12) 3))");
+
+    tree.remove(node);
+    assert_eq!(tree.fork_many(node, 0, vec![1], 0), None);
+}
+
+#[test]
+fn test_merge_with() {
+    use vec_tree::{ChildPlacement, MergeOptions};
+
+    let sexpr = |tree: &VecTree<String>| tree.to_sexpr(|data| data.clone());
+    let merged = |placement, collapse, node: &str, into: &str| {
+        let mut tree = VecTree::from_sexpr("(r (a a1 a2) (b b1 b2 b3) (c (d d1)))", |atom| atom.to_string()).unwrap();
+        let node_id = node_with_value(&tree, node.to_string());
+        let into_id = node_with_value(&tree, into.to_string());
+        let options = MergeOptions { placement, collapse };
+        assert!(tree.merge_with(node_id, into_id, options, |into, node| into.push_str(&node)).is_some());
+        sexpr(&tree)
+    };
+
+    assert_eq!(merged(ChildPlacement::Prepend, true, "a", "b"), "(r (ba a1 a2 b1 b2 b3) (c (d d1)))");
+    assert_eq!(merged(ChildPlacement::Append, true, "a", "b"), "(r (ba b1 b2 b3 a1 a2) (c (d d1)))");
+    assert_eq!(merged(ChildPlacement::DocumentOrder, true, "b", "a"), "(r (ab a1 a2 b1 b2 b3) (c (d d1)))");
+    assert_eq!(merged(ChildPlacement::DocumentOrder, true, "a", "b"), "(r (ba a1 a2 b1 b2 b3) (c (d d1)))");
+    assert_eq!(merged(ChildPlacement::Interleave, true, "b", "a"), "(r (ab a1 b1 a2 b2 b3) (c (d d1)))");
+    assert_eq!(merged(ChildPlacement::Interleave, true, "a", "b"), "(r (ba a1 b1 a2 b2 b3) (c (d d1)))");
+
+    // Merging across parents, collapsing the parent left with a single child or not.
+    assert_eq!(merged(ChildPlacement::Append, true, "d", "a"), "(r (ad a1 a2 d1) (b b1 b2 b3) c)");
+    assert_eq!(merged(ChildPlacement::Append, true, "a1", "b3"), "(r a2 (b b1 b2 b3a1) (c (d d1)))");
+    assert_eq!(merged(ChildPlacement::Append, false, "a1", "b3"), "(r (a a2) (b b1 b2 b3a1) (c (d d1)))");
+
+    // Nodes which are an ancestor of one another can't be merged.
+    let mut tree = VecTree::from_sexpr("(r (a a1) b)", |atom| atom.to_string()).unwrap();
+    let root = tree.get_root_index().unwrap();
+    let a = node_with_value(&tree, "a".to_string());
+    let a1 = node_with_value(&tree, "a1".to_string());
+    assert!(tree.merge_with(a1, a, MergeOptions::default(), |_, _| {}).is_none());
+    assert!(tree.merge_with(root, a1, MergeOptions::default(), |_, _| {}).is_none());
+    assert!(tree.merge_with(a, a, MergeOptions::default(), |_, _| {}).is_none());
+    assert_eq!(sexpr(&tree), "(r (a a1) b)");
+
+    // Collapsing the root makes the remaining child the root.
+    let b = node_with_value(&tree, "b".to_string());
+    assert!(tree.merge_with(b, a, MergeOptions::default(), |_, _| {}).is_some());
+    assert_eq!(tree.get_root_index(), Some(a));
+    assert!(!tree.contains(root));
+    assert_eq!(sexpr(&tree), "(a a1)");
+}
+
+#[test]
+fn test_merge_outcome() {
+    use vec_tree::{ChildPlacement, MergeOptions};
+
+    let mut tree = VecTree::from_sexpr("(r (p a b) c)", |atom| atom.to_string()).unwrap();
+    let p = node_with_value(&tree, "p".to_string());
+    let a = node_with_value(&tree, "a".to_string());
+    let b = node_with_value(&tree, "b".to_string());
+
+    let outcome = tree.merge(a, b).unwrap();
+    assert_eq!(outcome.surviving, b);
+    assert_eq!(outcome.removed, "a");
+    assert_eq!(outcome.removed_parent.as_deref(), Some("p"));
+    assert_eq!(outcome.lifted, Some(b));
+    assert!(!outcome.root_changed);
+    assert!(!tree.contains(p));
+    assert_eq!(tree.to_sexpr(|data| data.clone()), "(r b c)");
+
+    // Nodes that can't be merged give no outcome.
+    assert!(tree.merge(a, b).is_none());
+
+    // The closure of `merge_with` decides what is handed back.
+    let c = node_with_value(&tree, "c".to_string());
+    let options = MergeOptions {
+        placement: ChildPlacement::Append,
+        collapse: true,
+    };
+    let outcome = tree
+        .merge_with(c, b, options, |b, c| {
+            b.push_str(&c);
+            c.len()
+        })
+        .unwrap();
+    assert_eq!(outcome.removed, 1);
+    assert_eq!(outcome.removed_parent.as_deref(), Some("r"));
+    assert!(outcome.root_changed);
+    assert_eq!(tree.get_root_index(), Some(b));
+    assert_eq!(tree.to_sexpr(|data| data.clone()), "bc");
+
+    // Across parents, the node lifted in place of the collapsed parent is not the
+    // surviving one.
+    let mut tree = VecTree::from_sexpr("(r (p a b) (q c d))", |atom| atom.to_string()).unwrap();
+    let a = node_with_value(&tree, "a".to_string());
+    let b = node_with_value(&tree, "b".to_string());
+    let c = node_with_value(&tree, "c".to_string());
+    let outcome = tree.merge_with(a, c, MergeOptions::default(), |_, a| a).unwrap();
+    assert_eq!(outcome.surviving, c);
+    assert_eq!(outcome.removed_parent.as_deref(), Some("p"));
+    assert_eq!(outcome.lifted, Some(b));
+    assert_eq!(tree.to_sexpr(|data| data.clone()), "(r b (q c d))");
+}
+
+#[test]
+fn test_reorder_children() {
+    let sexpr = |tree: &VecTree<i32>| tree.to_sexpr(|data| data.to_string());
+    let mut tree = VecTree::from_sexpr("(0 (3 (6 9 8) 5) 1 2)", |atom| atom.parse::<i32>().unwrap()).unwrap();
+    let root = tree.get_root_index().unwrap();
+    let three = node_with_value(&tree, 3);
+
+    tree.sort_children_by(root, |a, b| a.cmp(b));
+    assert_eq!(sexpr(&tree), "(0 1 2 (3 (6 9 8) 5))");
+
+    // Sorting is stable.
+    tree.sort_children_by_key(root, |&data| data > 1);
+    assert_eq!(sexpr(&tree), "(0 1 2 (3 (6 9 8) 5))");
+    tree.sort_children_by_key(root, |&data| -data);
+    assert_eq!(sexpr(&tree), "(0 (3 (6 9 8) 5) 2 1)");
+
+    tree.reverse_children(root);
+    assert_eq!(sexpr(&tree), "(0 1 2 (3 (6 9 8) 5))");
+    assert_eq!(tree.parent(three), Some(root));
+
+    let one = node_with_value(&tree, 1);
+    let two = node_with_value(&tree, 2);
+    assert!(tree.swap_siblings(one, three));
+    assert_eq!(sexpr(&tree), "(0 (3 (6 9 8) 5) 2 1)");
+    assert!(tree.swap_siblings(two, one));
+    assert_eq!(sexpr(&tree), "(0 (3 (6 9 8) 5) 1 2)");
+    assert!(tree.swap_siblings(two, two));
+    assert_eq!(sexpr(&tree), "(0 (3 (6 9 8) 5) 1 2)");
+
+    // Nodes with different parents, or not in the tree, are left where they are.
+    let five = node_with_value(&tree, 5);
+    assert!(!tree.swap_siblings(two, five));
+    assert!(!tree.swap_siblings(root, one));
+    let removed = tree.insert(7, root);
+    tree.remove(removed);
+    assert!(!tree.swap_siblings(removed, one));
+    assert_eq!(sexpr(&tree), "(0 (3 (6 9 8) 5) 1 2)");
+
+    tree.sort_subtree_by(root, |a, b| a.cmp(b));
+    assert_eq!(sexpr(&tree), "(0 1 2 (3 5 (6 8 9)))");
+
+    // Appending after reordering uses the updated last child.
+    let appended = tree.insert(4, root);
+    assert_eq!(tree.children(root).last(), Some(appended));
+    assert_eq!(sexpr(&tree), "(0 1 2 (3 5 (6 8 9)) 4)");
+
+    // A leaf has no children to reorder.
+    tree.reverse_children(one);
+    assert_eq!(tree.children(one).count(), 0);
+}
+
+#[test]
+fn test_swap_positions_and_data() {
+    let sexpr = |tree: &VecTree<String>| tree.to_sexpr(|data| data.clone());
+    let mut tree = VecTree::from_sexpr("(r (a a1 a2) b (c c1) d)", |atom| atom.to_string()).unwrap();
+    let node = |tree: &VecTree<String>, value: &str| node_with_value(tree, value.to_string());
+
+    // Adjacent siblings, in both orders.
+    assert!(tree.swap_positions(node(&tree, "a"), node(&tree, "b")));
+    assert_eq!(sexpr(&tree), "(r b (a a1 a2) (c c1) d)");
+    assert!(tree.swap_positions(node(&tree, "a"), node(&tree, "b")));
+    assert_eq!(sexpr(&tree), "(r (a a1 a2) b (c c1) d)");
+
+    // Siblings apart from one another, first and last.
+    assert!(tree.swap_positions(node(&tree, "d"), node(&tree, "a")));
+    assert_eq!(sexpr(&tree), "(r d b (c c1) (a a1 a2))");
+
+    // Nodes with different parents.
+    assert!(tree.swap_positions(node(&tree, "a2"), node(&tree, "c")));
+    assert_eq!(sexpr(&tree), "(r d b a2 (a a1 (c c1)))");
+    assert!(tree.swap_positions(node(&tree, "c1"), node(&tree, "a1")));
+    assert_eq!(sexpr(&tree), "(r d b a2 (a c1 (c a1)))");
+
+    // Ancestors and descendants can't be swapped.
+    let root = tree.get_root_index().unwrap();
+    assert!(!tree.swap_positions(node(&tree, "a"), node(&tree, "a1")));
+    assert!(!tree.swap_positions(node(&tree, "a1"), node(&tree, "a")));
+    assert!(!tree.swap_positions(root, node(&tree, "d")));
+    assert!(tree.swap_positions(node(&tree, "d"), node(&tree, "d")));
+    assert_eq!(sexpr(&tree), "(r d b a2 (a c1 (c a1)))");
+
+    let d = node(&tree, "d");
+    let b = node(&tree, "b");
+    assert!(tree.swap_data(d, b));
+    assert_eq!(sexpr(&tree), "(r b d a2 (a c1 (c a1)))");
+    assert!(tree.swap_data(d, d));
+    tree.remove(d);
+    assert!(!tree.swap_data(d, b));
+    assert!(!tree.swap_positions(d, b));
+
+    // The links stay consistent at both ends of every list of children.
+    let a = node(&tree, "a");
+    tree.insert("e".to_string(), root);
+    tree.insert("a3".to_string(), a);
+    assert_eq!(sexpr(&tree), "(r d a2 (a c1 (c a1) a3) e)");
+}
+
+#[test]
+fn test_replace_and_replace_subtree() {
+    let sexpr = |tree: &VecTree<String>| tree.to_sexpr(|data| data.clone());
+    let parse = |input: &str| VecTree::from_sexpr(input, |atom| atom.to_string()).unwrap();
+
+    let mut tree = parse("(r (a a1) (b b1 b2) c)");
+    let a = node_with_value(&tree, "a".to_string());
+    assert_eq!(tree.replace(a, "A".to_string()).as_deref(), Some("a"));
+    assert_eq!(sexpr(&tree), "(r (A a1) (b b1 b2) c)");
+
+    // The new subtree keeps the sibling position of the old one.
+    let b = node_with_value(&tree, "b".to_string());
+    let (x, old) = tree.replace_subtree(b, parse("(x (y z))")).unwrap();
+    assert_eq!(sexpr(&tree), "(r (A a1) (x (y z)) c)");
+    assert_eq!(sexpr(&old), "(b b1 b2)");
+    assert!(!tree.contains(b));
+    assert_eq!(tree.children(x).count(), 1);
+
+    let a1 = node_with_value(&tree, "a1".to_string());
+    tree.replace_subtree(a1, parse("w")).unwrap();
+    assert_eq!(sexpr(&tree), "(r (A w) (x (y z)) c)");
+
+    // The root node can be replaced too.
+    let root = tree.get_root_index().unwrap();
+    let (new_root, old) = tree.replace_subtree(root, parse("(s t)")).unwrap();
+    assert_eq!(tree.get_root_index(), Some(new_root));
+    assert_eq!(sexpr(&tree), "(s t)");
+    assert_eq!(sexpr(&old), "(r (A w) (x (y z)) c)");
+    assert_eq!(tree.len(), 2);
+
+    // The other tree is given back when nothing can be replaced.
+    let given_back = tree.replace_subtree(root, parse("(u v)")).unwrap_err();
+    assert_eq!(sexpr(&given_back), "(u v)");
+    assert!(tree.replace_subtree(new_root, VecTree::new()).is_err());
+    assert_eq!(tree.replace(root, "r".to_string()), None);
+    assert_eq!(sexpr(&tree), "(s t)");
+}
+
+#[test]
+fn test_retain() {
+    let sexpr = |tree: &VecTree<i32>| tree.to_sexpr(|data| data.to_string());
+    let parse = |input: &str| VecTree::from_sexpr(input, |atom| atom.parse::<i32>().unwrap()).unwrap();
+
+    let mut tree = parse("(1 (2 (3 4)) (5 6 7) 8)");
+    let mut visited = Vec::new();
+    tree.retain(|_, &data| {
+        visited.push(data);
+        data % 3 != 2
+    });
+    assert_eq!(visited, [1, 2, 5, 8]);
+    assert_eq!(sexpr(&tree), "1");
+    assert_eq!(tree.len(), 1);
+
+    let mut tree = parse("(1 (2 (3 4)) (5 6 7) 8)");
+    tree.retain(|_, &data| data != 1);
+    assert!(tree.is_empty());
+    assert_eq!(tree.get_root_index(), None);
+
+    let mut tree = parse("(1 (2 (3 4)) (5 6 7) 8)");
+    let mut visited = Vec::new();
+    tree.retain_lifting(|_, &data| {
+        visited.push(data);
+        data % 3 != 2
+    });
+    assert_eq!(visited, [4, 3, 2, 6, 7, 5, 8, 1]);
+    assert_eq!(sexpr(&tree), "(1 (3 4) 6 7)");
+
+    // The root is kept when it has several children left, replaced otherwise.
+    let mut tree = parse("(1 (2 (3 4)) (5 6 7) 8)");
+    tree.retain_lifting(|_, &data| data % 2 == 0);
+    assert_eq!(sexpr(&tree), "(1 (2 4) 6 8)");
+
+    let mut tree = parse("(1 (2 (3 4)) 5)");
+    let four = node_with_value(&tree, 4);
+    tree.retain_lifting(|_, &data| data == 4);
+    assert_eq!(tree.get_root_index(), Some(four));
+    assert_eq!(sexpr(&tree), "4");
+
+    let mut tree = parse("(1 2 3)");
+    tree.retain_lifting(|_, _| false);
+    assert!(tree.is_empty());
+}
+
+#[test]
+fn test_map_preserves_indices() {
+    let mut tree = VecTree::from_sexpr("(1 (2 3 4) 5)", |atom| atom.parse::<i32>().unwrap()).unwrap();
+    // Leave free slots behind, so that the indices are not just in insertion order.
+    let two = node_with_value(&tree, 2);
+    let three = node_with_value(&tree, 3);
+    tree.remove(three);
+    let six = tree.insert(6, two);
+    let seven = tree.insert(7, two);
+    tree.remove(seven);
+
+    let mut seen = Vec::new();
+    let strings = tree.map(|index, &data| {
+        seen.push(index);
+        data.to_string()
+    });
+    seen.sort();
+    let mut indices = tree.descendants(tree.get_root_index().unwrap()).collect::<Vec<_>>();
+    indices.sort();
+    assert_eq!(seen, indices);
+
+    assert_eq!(strings.get_root_index(), tree.get_root_index());
+    for &index in &indices {
+        assert_eq!(strings[index], tree[index].to_string());
+        assert_eq!(strings.parent(index), tree.parent(index));
+        assert_eq!(strings.children(index).collect::<Vec<_>>(), tree.children(index).collect::<Vec<_>>());
+    }
+    assert!(!strings.contains(three));
+    assert!(!strings.contains(seven));
+    assert_eq!(strings.to_sexpr(|data| data.clone()), "(1 (2 4 6) 5)");
+
+    let doubled = tree.into_map(|_, data| data * 2);
+    assert_eq!(doubled[six], 12);
+    assert_eq!(doubled.to_sexpr(|data| data.to_string()), "(2 (4 8 12) 10)");
+
+    // Both trees keep working on their own afterwards.
+    let mut strings = strings;
+    let eight = strings.insert("8".to_string(), six);
+    assert_eq!(strings.parent(eight), Some(six));
+    assert_eq!(doubled.len(), 5);
+}
+
+#[test]
+fn test_fold_up_and_down() {
+    let tree = VecTree::from_sexpr("(1 (2 (3 4 5)) 6 (7 8))", |atom| atom.parse::<i32>().unwrap()).unwrap();
+    let root = tree.get_root_index().unwrap();
+    let two = node_with_value(&tree, 2);
+    let three = node_with_value(&tree, 3);
+    let five = node_with_value(&tree, 5);
+
+    let sizes = tree.fold_up(root, |_, _| 1, |_, _, children| 1 + children.iter().sum::<usize>());
+    assert_eq!(sizes.len(), 8);
+    assert_eq!(sizes[&root], 8);
+    assert_eq!(sizes[&two], 4);
+    assert_eq!(sizes[&five], 1);
+
+    // The children's values come in order.
+    let sexprs = tree.fold_up(
+        three,
+        |_, data| data.to_string(),
+        |_, data, children| format!("({} {})", data, children.join(" ")),
+    );
+    assert_eq!(sexprs.len(), 3);
+    assert_eq!(sexprs[&three], "(3 4 5)");
+
+    let depths = tree.fold_down(root, -1, |&depth, _, _| depth + 1);
+    assert_eq!(depths.len(), 8);
+    assert_eq!(depths[&root], 0);
+    assert_eq!(depths[&five], 3);
+    for (node_id, depth) in tree.descendants_with_depth(root) {
+        assert_eq!(depths[&node_id], depth as i32);
+    }
+
+    let sums = tree.fold_down(two, 100, |&sum, _, &data| sum + data);
+    assert_eq!(sums.len(), 4);
+    assert_eq!(sums[&five], 110);
+
+    // Deep trees don't overflow the stack.
+    let mut deep = VecTree::new();
+    let mut node = deep.insert_root(0);
+    let deep_root = node;
+    for i in 1..100_000 {
+        node = deep.insert(i, node);
+    }
+    let heights = deep.fold_up(deep_root, |_, _| 0, |_, _, children| children[0] + 1);
+    assert_eq!(heights[&deep_root], 99_999);
+    let depths = deep.fold_down(deep_root, 0, |&depth, _, _| depth + 1);
+    assert_eq!(depths[&node], 100_000);
+}
+
+#[test]
+fn test_augmented_tree() {
+    use vec_tree::augmented::{AugmentedVecTree, Measured, Monoid};
+
+    // The sum, minimum and maximum of the costs in a subtree.
+    #[derive(Debug, PartialEq)]
+    struct Stats {
+        sum: i64,
+        min: i64,
+        max: i64,
+    }
+
+    impl Monoid for Stats {
+        fn empty() -> Self {
+            Stats {
+                sum: 0,
+                min: i64::MAX,
+                max: i64::MIN,
+            }
+        }
+
+        fn combine(&self, other: &Self) -> Self {
+            Stats {
+                sum: self.sum + other.sum,
+                min: self.min.min(other.min),
+                max: self.max.max(other.max),
+            }
+        }
+    }
+
+    impl Measured<Stats> for i64 {
+        fn measure(&self) -> Stats {
+            Stats {
+                sum: *self,
+                min: *self,
+                max: *self,
+            }
+        }
+    }
+
+    let stats = |sum, min, max| Some(Stats { sum, min, max });
+    let mut tree = AugmentedVecTree::new();
+    let root = tree.insert_root(1);
+    let a = tree.insert(2, root);
+    let a1 = tree.insert(3, a);
+    let b = tree.insert(4, root);
+    assert_eq!(tree.aggregate(root), stats(10, 1, 4).as_ref());
+    assert_eq!(tree.aggregate(a), stats(5, 2, 3).as_ref());
+
+    *tree.get_mut(a1).unwrap() = -5;
+    assert_eq!(tree.aggregate(a), stats(-3, -5, 2).as_ref());
+    assert_eq!(tree.aggregate(root), stats(2, -5, 4).as_ref());
+
+    tree.append_child(b, a1);
+    assert_eq!(tree.aggregate(a), stats(2, 2, 2).as_ref());
+    assert_eq!(tree.aggregate(b), stats(-1, -5, 4).as_ref());
+    assert_eq!(tree.aggregate(root), stats(2, -5, 4).as_ref());
+
+    let sibling = tree.fork(a, 10, 20).unwrap();
+    let new_parent = tree.tree().parent(a).unwrap();
+    assert_eq!(tree.aggregate(sibling), stats(20, 20, 20).as_ref());
+    assert_eq!(tree.aggregate(new_parent), stats(32, 2, 20).as_ref());
+    assert_eq!(tree.aggregate(root), stats(32, -5, 20).as_ref());
+
+    let outcome = tree.merge(a, sibling).unwrap();
+    assert_eq!(outcome.removed, 2);
+    assert_eq!(outcome.removed_parent, Some(10));
+    assert_eq!(tree.aggregate(a), None);
+    assert_eq!(tree.aggregate(new_parent), None);
+    assert_eq!(tree.aggregate(root), stats(20, -5, 20).as_ref());
+
+    assert_eq!(tree.remove(b), Some(4));
+    assert_eq!(tree.aggregate(a1), None);
+    assert_eq!(tree.aggregate(root), stats(21, 1, 20).as_ref());
+
+    let new_root = tree.insert_root(-10);
+    assert_eq!(tree.aggregate(new_root), stats(11, -10, 20).as_ref());
+    assert_eq!(tree[new_root], -10);
+    assert_eq!(tree.tree().len(), 3);
+}
+
+#[test]
+#[should_panic]
+fn test_augmented_append_child_below_itself() {
+    use vec_tree::augmented::{AugmentedVecTree, Measured, Monoid};
+
+    struct Count(usize);
+
+    impl Monoid for Count {
+        fn empty() -> Self {
+            Count(0)
+        }
+
+        fn combine(&self, other: &Self) -> Self {
+            Count(self.0 + other.0)
+        }
+    }
+
+    impl Measured<Count> for char {
+        fn measure(&self) -> Count {
+            Count(1)
+        }
+    }
+
+    let mut tree = AugmentedVecTree::new();
+    let root = tree.insert_root('r');
+    let a = tree.insert('a', root);
+    let a1 = tree.insert('b', a);
+    tree.append_child(a1, a);
+}
+
+#[test]
+fn test_find_and_resolve_path() {
+    #[derive(Debug)]
+    struct Entry {
+        name: String,
+        size: u64,
+    }
+
+    let mut tree = VecTree::new();
+    let entry = |name: &str, size| Entry {
+        name: name.to_string(),
+        size,
+    };
+    let root = tree.insert_root(entry("/", 0));
+    let etc = tree.insert(entry("etc", 0), root);
+    let hosts = tree.insert(entry("hosts", 120), etc);
+    let home = tree.insert(entry("home", 0), root);
+    let user = tree.insert(entry("user", 0), home);
+    let notes = tree.insert(entry("notes", 300), user);
+    let other_hosts = tree.insert(entry("hosts", 80), user);
+
+    assert_eq!(tree.find(root, |_, entry| entry.name == "hosts"), Some(hosts));
+    assert_eq!(tree.find(home, |_, entry| entry.name == "hosts"), Some(other_hosts));
+    assert_eq!(tree.find(root, |node, _| node == root), Some(root));
+    assert_eq!(tree.find(etc, |_, entry| entry.name == "notes"), None);
+
+    let files = tree.find_all(root, |_, entry| entry.size > 0).collect::<Vec<_>>();
+    assert_eq!(files, [hosts, notes, other_hosts]);
+    assert_eq!(tree.find_all(home, |_, entry| entry.size > 100).collect::<Vec<_>>(), [notes]);
+    assert_eq!(tree.find_all(etc, |_, entry| entry.size == 0).collect::<Vec<_>>(), [etc]);
+
+    assert_eq!(tree.position_child(root, |_, entry| entry.name == "home"), Some(1));
+    assert_eq!(tree.position_child(user, |_, entry| entry.size < 100), Some(1));
+    assert_eq!(tree.position_child(hosts, |_, _| true), None);
+
+    fn name(entry: &Entry) -> &str {
+        &entry.name
+    }
+    assert_eq!(tree.resolve_path(root, &["home", "user", "notes"], name), Some(notes));
+    assert_eq!(tree.resolve_path(home, &["user", "hosts"], name), Some(other_hosts));
+    assert_eq!(tree.resolve_path(root, &["home", "notes"], name), None);
+    assert_eq!(tree.resolve_path(root, &["etc"], name), Some(etc));
+    assert_eq!(tree.resolve_path(user, &[], name), Some(user));
+    let path = ["home".to_string(), "user".to_string()];
+    let path = path.iter().map(String::as_str).collect::<Vec<_>>();
+    assert_eq!(tree.resolve_path(root, &path, name), Some(user));
+}
+
+#[test]
+fn test_selector() {
+    use vec_tree::selector::{ParseError, ParseErrorKind, Selector};
+
+    let tree = VecTree::from_sexpr(
+        "(html (head title) (body (p a b) (div (p c) (span d)) (p e) (div f)))",
+        |atom| atom.to_string(),
+    )
+    .unwrap();
+    let root = tree.get_root_index().unwrap();
+    let select = |selector: &str, node_id| {
+        let selector: Selector = selector.parse().unwrap();
+        selector
+            .select(&tree, node_id, String::as_str)
+            .into_iter()
+            .map(|node_id| tree.subtree_to_sexpr(node_id, |data| data.clone()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(select("body/p", root), ["(p a b)", "(p e)"]);
+    assert_eq!(select("child::body/child::p[2]", root), ["(p e)"]);
+    assert_eq!(select("body/*[2]", root), ["(div (p c) (span d))"]);
+    assert_eq!(select("*/*/*", root), ["a", "b", "(p c)", "(span d)", "e", "f"]);
+    assert_eq!(select("descendant::p", root), ["(p a b)", "(p c)", "(p e)"]);
+    assert_eq!(select("//p[1]", root), ["(p a b)", "(p c)"]);
+    assert_eq!(select("descendant::p[1]", root), ["(p a b)"]);
+    assert_eq!(select("body//span/..", root), ["(div (p c) (span d))"]);
+    assert_eq!(select("body/div/p/parent::div/following-sibling::*", root), ["(p e)", "(div f)"]);
+    assert_eq!(select("body/div[2]/preceding-sibling::*[1]", root), ["(p e)"]);
+    assert_eq!(select("body/p[2]/preceding-sibling::p", root), ["(p a b)"]);
+    assert_eq!(select(".", root), [tree.to_sexpr(|data| data.clone())]);
+    assert_eq!(select("self::body", root), Vec::<String>::new());
+    assert_eq!(select("head/title", root), ["title"]);
+    assert_eq!(select("missing", root), Vec::<String>::new());
+
+    // Selectors run from any node, and give every node once, in tree order.
+    let d = tree.find(root, |_, data| data == "d").unwrap();
+    let ancestors = select("ancestor::*", d);
+    assert_eq!(ancestors.len(), 4);
+    assert!(ancestors[0].starts_with("(html "));
+    assert!(ancestors[1].starts_with("(body "));
+    assert_eq!(ancestors[2..], ["(div (p c) (span d))", "(span d)"]);
+    assert_eq!(select("ancestor::*[2]", d), ["(div (p c) (span d))"]);
+    let c = tree.find(root, |_, data| data == "c").unwrap();
+    assert_eq!(select("../../following-sibling::*", c), ["(p e)", "(div f)"]);
+    assert_eq!(select("../following-sibling::*/preceding-sibling::*", c), ["(p c)"]);
+    assert_eq!(select("ancestor::body//p", d), ["(p a b)", "(p c)", "(p e)"]);
+    assert_eq!(select("//p/../*", root).len(), 6);
+
+    let error = |selector: &str| Selector::parse(selector).unwrap_err();
+    assert_eq!(error("a/"), ParseError { position: 2, kind: ParseErrorKind::UnexpectedEnd });
+    assert_eq!(error(""), ParseError { position: 0, kind: ParseErrorKind::UnexpectedEnd });
+    assert_eq!(error("a b"), ParseError { position: 1, kind: ParseErrorKind::UnexpectedCharacter(' ') });
+    assert_eq!(error("a/sideways::b"), ParseError { position: 2, kind: ParseErrorKind::UnknownAxis });
+    assert_eq!(error("a[0]"), ParseError { position: 2, kind: ParseErrorKind::InvalidPosition });
+    assert_eq!(error("a[+1]"), ParseError { position: 2, kind: ParseErrorKind::InvalidPosition });
+    assert_eq!(error("a[1"), ParseError { position: 3, kind: ParseErrorKind::UnexpectedEnd });
+    assert_eq!(error("..[1]"), ParseError { position: 2, kind: ParseErrorKind::UnexpectedCharacter('[') });
+}
+
+#[test]
+fn test_keyed_tree() {
+    use vec_tree::keyed::KeyedVecTree;
+
+    let mut tree = KeyedVecTree::new();
+    let root = tree.insert_root(0).unwrap();
+    assert_eq!(tree.insert_root(1), Err(1));
+    assert_eq!(tree.key(root), None);
+
+    let a = tree.insert_keyed("a".to_string(), 1, root).unwrap();
+    let b = tree.insert_keyed("b".to_string(), 2, root).unwrap();
+    let a1 = tree.insert_keyed("x".to_string(), 3, a).unwrap();
+    let b1 = tree.insert_keyed("x".to_string(), 4, b).unwrap();
+    assert_eq!(tree.child_by_key(root, "a"), Some(a));
+    assert_eq!(tree.child_by_key(a, "x"), Some(a1));
+    assert_eq!(tree.child_by_key(b, "x"), Some(b1));
+    assert_eq!(tree.child_by_key(a1, "x"), None);
+    assert_eq!(tree.key(b).map(String::as_str), Some("b"));
+    assert_eq!(tree.insert_keyed("a".to_string(), 5, root), Err(("a".to_string(), 5)));
+    assert_eq!(tree.tree().children(root).collect::<Vec<_>>(), [a, b]);
+
+    // Renaming keeps the order of the children.
+    assert_eq!(tree.rename(b, "a".to_string()), Err("a".to_string()));
+    assert_eq!(tree.rename(root, "c".to_string()), Err("c".to_string()));
+    assert_eq!(tree.rename(b, "b".to_string()), Ok("b".to_string()));
+    assert_eq!(tree.rename(a, "c".to_string()), Ok("a".to_string()));
+    assert_eq!(tree.child_by_key(root, "a"), None);
+    assert_eq!(tree.child_by_key(root, "c"), Some(a));
+    assert_eq!(tree.tree().children(root).collect::<Vec<_>>(), [a, b]);
+
+    // Removing a node frees its key and drops the keys below it.
+    tree[a1] += 10;
+    assert_eq!(tree.remove(a), Some(1));
+    assert_eq!(tree.child_by_key(root, "c"), None);
+    assert_eq!(tree.key(a1), None);
+    assert_eq!(tree.remove(a), None);
+    let c = tree.insert_keyed("c".to_string(), 6, root).unwrap();
+    assert_eq!(tree.child_by_key(root, "c"), Some(c));
+
+    let tree = tree.into_tree();
+    assert_eq!(tree.len(), 4);
+}
+
+#[test]
+fn test_indexed_children() {
+    use vec_tree::diff::{Edit, NodeRef};
+
+    fn check(tree: &VecTree<i32>, parent: Index) {
+        let children = tree.children(parent).collect::<Vec<_>>();
+        for (position, &child) in children.iter().enumerate() {
+            assert_eq!(tree.nth_child(parent, position), Some(child));
+            assert_eq!(tree.child_index_of(child), Some(position));
+        }
+        assert_eq!(tree.nth_child(parent, children.len()), None);
+        let middle = children.len() / 2;
+        assert_eq!(
+            tree.children_range(parent, middle..middle + 3).collect::<Vec<_>>(),
+            &children[middle..(middle + 3).min(children.len())]
+        );
+    }
+
+    let mut tree = VecTree::new();
+    let root = tree.insert_root(0);
+    let other = tree.insert(-1, root);
+    assert!(tree.index_children(other));
+    let children = (1..=200).map(|i| tree.insert(i, other)).collect::<Vec<_>>();
+    check(&tree, other);
+
+    // Moves within the same parent, from another parent, and out of it.
+    let move_into = |node, after: Option<Index>| Edit::Move {
+        node,
+        parent: Some(NodeRef::Existing(other)),
+        after: after.map(NodeRef::Existing),
+    };
+    tree.apply_patch(vec![
+        move_into(children[150], Some(children[10])),
+        move_into(children[199], Some(children[2])),
+        move_into(children[100], None),
+    ]);
+    tree.append_child(other, children[0]);
+    let outsider = tree.insert(1000, root);
+    tree.apply_patch(vec![move_into(outsider, Some(children[49]))]);
+    tree.append_child(root, children[60]);
+    check(&tree, other);
+
+    // Removals, reorderings and replacements of whole lists of children.
+    tree.remove(children[20]);
+    tree.remove_and_lift_children(children[30]);
+    tree.sort_children_by_key(other, |&value| -value);
+    check(&tree, other);
+    tree.reverse_children(other);
+    assert!(tree.swap_siblings(children[5], children[6]));
+    tree.swap_positions(children[7], children[60]);
+    check(&tree, other);
+    let order = tree.children(other).collect::<Vec<_>>();
+    assert!(tree.swap_siblings(order[0], order[order.len() - 1]));
+    assert!(tree.swap_siblings(order[11], order[10]));
+    assert!(tree.swap_siblings(order[20], order[21]));
+    check(&tree, other);
+    let wrapper = tree.wrap(children[40], children[45], 500).unwrap();
+    tree.retain(|_, &value| value % 11 != 4);
+    check(&tree, other);
+
+    // The index follows the nodes through compaction, and goes with them.
+    let remap = tree.compact();
+    let (root, other) = (remap[root], remap[other]);
+    check(&tree, other);
+    assert_eq!(tree.child_index_of(remap[wrapper]), tree.children(other).position(|id| id == remap[wrapper]));
+    assert!(tree.unindex_children(other));
+    assert!(!tree.unindex_children(other));
+    check(&tree, other);
+    assert!(tree.index_children(other));
+    tree.remove(other);
+    assert!(!tree.index_children(other));
+    assert_eq!(tree.nth_child(other, 0), None);
+    assert_eq!(tree.children_range(other, 0..3).count(), 0);
+    assert_eq!(tree.child_index_of(root), None);
+    assert_eq!(tree.children_range(root, 5..5).count(), 0);
+}